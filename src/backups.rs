@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::prelude::*;
+
+use crate::clock::Clock;
+use crate::config::BackupConfig;
+use crate::config::BackupInterval;
+use crate::config::Config;
+use crate::config::Location;
+use crate::error::Result;
+use crate::error::SqError;
+use crate::notice;
+use crate::storage;
+use crate::storage::Storage;
+use crate::types::State;
+
+/// Format of the timestamp that identifies a backup.
+const ID_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Rotating copies of the state file, kept in a `backups` directory next to
+/// it and named after it, ie `backups/state-20210618T120000.json`.
+pub struct Backups {
+    dir: PathBuf,
+    config: BackupConfig,
+}
+
+/// A single backup, identified by when it was taken.
+pub struct Backup {
+    pub id: String,
+    pub path: PathBuf,
+}
+
+impl Backups {
+    #[must_use]
+    pub fn new(dir: &Path, config: BackupConfig) -> Self {
+        Self {
+            dir: dir.join("backups"),
+            config,
+        }
+    }
+
+    /// Copy the state file of `store` into the backup directory, if one is
+    /// due, and remove backups beyond the configured number. Should be called
+    /// while holding the storage lock, before the state changes. Backups are
+    /// named after what `clock` says it is.
+    pub fn take(&self, store: &dyn Storage, clock: &dyn Clock) -> Result<()> {
+        if self.config.keep == 0 || !store.path().exists() {
+            return Ok(());
+        }
+
+        let now = clock.now();
+        let existing = self.list(store)?;
+        let due = match self.config.every {
+            BackupInterval::Mutation => true,
+            BackupInterval::Day => {
+                let today = now.format("%Y%m%d").to_string();
+                !existing.iter().any(|b| b.id.starts_with(&today))
+            }
+        };
+
+        if due {
+            let id = now.format(ID_FORMAT).to_string();
+            let target = self.path_for(store, &id);
+            if !target.exists() {
+                fs::create_dir_all(&self.dir)?;
+                fs::copy(store.path(), target)?;
+            }
+        }
+
+        for old in self.list(store)?.iter().skip(self.config.keep) {
+            fs::remove_file(&old.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Backups of the state file of `store`, newest first.
+    pub fn list(&self, store: &dyn Storage) -> Result<Vec<Backup>> {
+        let (stem, ext) = name_parts(store.path());
+        let prefix = format!("{stem}-");
+        let suffix = format!(".{ext}");
+
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut found = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(id) = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(&suffix))
+            {
+                if NaiveDateTime::parse_from_str(id, ID_FORMAT).is_ok() {
+                    found.push(Backup {
+                        id: id.to_string(),
+                        path: entry.path(),
+                    });
+                }
+            }
+        }
+        found.sort_by(|a, b| b.id.cmp(&a.id));
+
+        Ok(found)
+    }
+
+    /// The backup of the state file of `store` with the given `id`.
+    pub fn find(&self, store: &dyn Storage, id: &str) -> Result<Backup> {
+        self.list(store)?
+            .into_iter()
+            .find(|b| b.id == id)
+            .ok_or_else(|| {
+                SqError::Validation(format!("No backup with ID {id}, see `sq backup list`."))
+            })
+    }
+
+    fn path_for(&self, store: &dyn Storage, id: &str) -> PathBuf {
+        let (stem, ext) = name_parts(store.path());
+        self.dir.join(format!("{stem}-{id}.{ext}"))
+    }
+}
+
+fn name_parts(path: &Path) -> (String, String) {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    (stem.to_string(), ext.to_string())
+}
+
+/// Load a backup the same way the real state gets loaded, migrations and all.
+/// This happens on a scratch copy, so the backup itself stays untouched.
+pub fn validate(backup: &Path) -> Result<State> {
+    let scratch = std::env::temp_dir().join(format!("sq-restore-{}", std::process::id()));
+    fs::create_dir_all(&scratch)?;
+    let copy = scratch.join(backup.file_name().unwrap_or_default());
+    fs::copy(backup, &copy)?;
+
+    let loaded = storage::open(&Location::File(copy), &Config::default(), &notice::ignore)
+        .and_then(|s| s.load(&notice::ignore))
+        .and_then(|state| state.ok_or_else(|| SqError::Storage("It's empty.".to_string())));
+    fs::remove_dir_all(&scratch)?;
+
+    loaded.map_err(|err| {
+        SqError::Storage(format!(
+            "Backup {} can't be loaded: {err}",
+            backup.display()
+        ))
+    })
+}
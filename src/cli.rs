@@ -0,0 +1,10 @@
+//! The parts of the command line that live outside of `main.rs`.
+pub mod backups;
+pub mod balance;
+pub mod income;
+pub mod io;
+pub mod items;
+pub mod output;
+pub mod policy;
+pub mod queues;
+pub mod simulate;
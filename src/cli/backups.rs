@@ -0,0 +1,200 @@
+use std::collections::BTreeSet;
+use std::collections::VecDeque;
+use std::fs;
+
+use ansi_term::Style;
+use clap::{App, Arg};
+use fraction::Zero;
+use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
+use prettytable::row;
+use prettytable::Table;
+use serde_json::json;
+
+use sq::backups;
+use sq::types::Item;
+use sq::types::Queue;
+use sq::types::State;
+use sq::Result;
+use sq::SqError;
+
+use crate::cli::io::yes_no_predicate;
+use crate::cli::output;
+use crate::session;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("backup")
+        .about("List and restore automatic backups of the state")
+        .subcommand(App::new("list").about("List available backups, newest first"))
+        .subcommand(
+            App::new("restore")
+                .about("Replace the state with a backup")
+                .arg(
+                    Arg::with_name("id")
+                        .help("Which backup to restore, as shown by `sq backup list`")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .help("Don't ask for confirmation")
+                        .short("y")
+                        .long("yes")
+                        .takes_value(false),
+                ),
+        )
+}
+
+pub fn cmd_backup(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", _) => cmd_backup_list(),
+        ("restore", Some(m)) => cmd_backup_restore(m.value_of("id").unwrap(), m.is_present("yes")),
+        _ => {
+            eprintln!("{}", matches.usage());
+            Ok(())
+        }
+    }
+}
+
+fn cmd_backup_list() -> Result<()> {
+    let session = session()?;
+    let store = session.storage()?;
+    if !output::is_text() {
+        let mut records = Vec::new();
+        for backup in session.backups().list(&*store)? {
+            records.push(json!({
+                "id": backup.id,
+                "path": backup.path,
+                "size": fs::metadata(&backup.path)?.len(),
+            }));
+        }
+        output::print_records(&records, &["id", "path", "size"]);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!("ID", "Size"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    for backup in session.backups().list(&*store)? {
+        let size = format!("{} KiB", fs::metadata(&backup.path)?.len().div_ceil(1024));
+        table.add_row(row!(b->backup.id, r->size));
+    }
+
+    table.printstd();
+    println!();
+    Ok(())
+}
+
+/// Restore a backup, after checking that it loads just like the real state
+/// would and showing what would change. The state it replaces goes into the
+/// journal, so a restore can be undone like any other change. The current
+/// state doesn't have to be readable, restoring is how to get out of that.
+fn cmd_backup_restore(id: &str, yes: bool) -> Result<()> {
+    let session = session()?;
+    let backup = session.backups().find(&*session.storage()?, id)?;
+
+    let restored = backups::validate(&backup.path)?;
+    if output::is_text() || !yes {
+        let current = match session.load() {
+            Ok(current) => Some(current),
+            Err(SqError::Corrupt { .. }) => None,
+            Err(err) => return Err(err),
+        };
+        print_diff(current.as_ref(), &restored);
+    }
+
+    if !yes && !yes_no_predicate("Restore this backup?")? {
+        return Ok(());
+    }
+
+    session.replace_state("backup restore", restored)?;
+    if output::is_text() {
+        println!("Restored backup {}.", Style::new().bold().paint(id));
+    } else {
+        let record = json!({ "event": "backup_restored", "id": id });
+        output::print_record(&record, &["event", "id"]);
+    }
+
+    Ok(())
+}
+
+/// Summarize how `restored` differs from `current`, queue by queue. Without a
+/// readable current state, everything changes. Outside of text output, this
+/// goes to stderr, so only the record of the restore ends up on stdout.
+fn print_diff(current: Option<&State>, restored: &State) {
+    let say = |line: &str| {
+        if output::is_text() {
+            println!("{line}");
+        } else {
+            eprintln!("{line}");
+        }
+    };
+    let bold = Style::new().bold();
+    let mut changed = false;
+    if current.is_none() {
+        say("The current state can't be read, all of it would be replaced.");
+    }
+    let current: &[Queue] = current.map_or(&[], |state| &state.queues);
+
+    for queue in current {
+        if !restored.queues.iter().any(|q| q.name == queue.name) {
+            say(&format!(
+                "Queue {} would be removed.",
+                bold.paint(&queue.name)
+            ));
+            changed = true;
+        }
+    }
+
+    for queue in &restored.queues {
+        let Some(now) = current.iter().find(|q| q.name == queue.name) else {
+            say(&format!(
+                "Queue {} would be added, with {} items.",
+                bold.paint(&queue.name),
+                queue.future_purchases.len()
+            ));
+            changed = true;
+            continue;
+        };
+
+        let mut lines = Vec::new();
+        if !(now.current_balance - queue.current_balance).is_zero() {
+            lines.push(format!(
+                "balance ${:#.2} -> ${:#.2}",
+                now.current_balance, queue.current_balance
+            ));
+        }
+
+        let names = |items: &VecDeque<Item>| {
+            items
+                .iter()
+                .map(|i| i.name.clone())
+                .collect::<BTreeSet<String>>()
+        };
+        let (before, after) = (names(&now.future_purchases), names(&queue.future_purchases));
+        for gone in before.difference(&after) {
+            lines.push(format!("item {gone} would be removed"));
+        }
+        for new in after.difference(&before) {
+            lines.push(format!("item {new} would be added"));
+        }
+        if now.past_purchases.len() != queue.past_purchases.len() {
+            lines.push(format!(
+                "{} past purchases -> {}",
+                now.past_purchases.len(),
+                queue.past_purchases.len()
+            ));
+        }
+
+        if !lines.is_empty() {
+            changed = true;
+            say(&format!("Queue {}:", bold.paint(&queue.name)));
+            for line in lines {
+                say(&format!("  {line}"));
+            }
+        }
+    }
+
+    if !changed {
+        say("The backup has the same queues and items as the current state.");
+    }
+}
@@ -0,0 +1,104 @@
+use clap::{App, Arg};
+
+use sq::engine;
+use sq::types::M;
+use sq::Result;
+
+use crate::cli::io::parse_money;
+use crate::report;
+use crate::session;
+
+fn amount_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("amount")
+        .help(help)
+        .required(true)
+        .takes_value(true)
+}
+
+fn note_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("note")
+        .help(help)
+        .short("n")
+        .long("note")
+        .takes_value(true)
+}
+
+fn force_arg() -> Arg<'static, 'static> {
+    Arg::with_name("force")
+        .help("Go below zero if there isn't enough, as far as `sq policy --overdraft` allows")
+        .short("f")
+        .long("force")
+}
+
+pub fn deposit_subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("deposit")
+        .about("Put money into the queue by hand, ie a gift card")
+        .arg(amount_arg("How much to put in"))
+        .arg(note_arg("Where the money came from, kept in the ledger"))
+}
+
+pub fn withdraw_subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("withdraw")
+        .about("Take money out of the queue by hand, ie for something bought elsewhere")
+        .arg(amount_arg("How much to take out"))
+        .arg(note_arg("What the money was for, kept in the ledger"))
+        .arg(force_arg())
+}
+
+pub fn transfer_subcommand<'a, 'b>() -> App<'a, 'b> {
+    let queue = |name, help| {
+        Arg::with_name(name)
+            .help(help)
+            .required(true)
+            .takes_value(true)
+    };
+
+    App::new("transfer")
+        .about("Move money from one queue to another")
+        .arg(queue("from", "Queue to take the money from"))
+        .arg(queue("to", "Queue to put the money into"))
+        .arg(amount_arg("How much to move"))
+        .arg(note_arg("Why, kept in the ledger of both queues"))
+        .arg(force_arg())
+}
+
+pub fn cmd_deposit(matches: &clap::ArgMatches) -> Result<()> {
+    let amount = parse_amount(matches.value_of("amount").unwrap())?;
+    let note = matches.value_of("note");
+    let session = session()?;
+    let event = session.update_selected_queue("deposit", |queue| {
+        engine::deposit(queue, amount, note, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+pub fn cmd_withdraw(matches: &clap::ArgMatches) -> Result<()> {
+    let amount = parse_amount(matches.value_of("amount").unwrap())?;
+    let note = matches.value_of("note");
+    let force = matches.is_present("force");
+    let session = session()?;
+    let event = session.update_selected_queue("withdraw", |queue| {
+        engine::withdraw(queue, amount, note, force, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+pub fn cmd_transfer(matches: &clap::ArgMatches) -> Result<()> {
+    let from = matches.value_of("from").unwrap();
+    let to = matches.value_of("to").unwrap();
+    let amount = parse_amount(matches.value_of("amount").unwrap())?;
+    let note = matches.value_of("note");
+    let force = matches.is_present("force");
+    let session = session()?;
+    let event = session.update_state("transfer", |state| {
+        engine::transfer(state, from, to, amount, note, force, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+fn parse_amount(input: &str) -> Result<M> {
+    parse_money(input, "amount")
+}
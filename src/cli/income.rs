@@ -0,0 +1,212 @@
+use clap::{App, Arg, ArgGroup};
+use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
+use prettytable::row;
+use prettytable::Table;
+use serde_json::json;
+use serde_json::Value;
+
+use sq::engine;
+use sq::types::AllocationRule;
+use sq::types::Income;
+use sq::types::Share;
+use sq::types::SharedIncome;
+use sq::types::State;
+use sq::{Result, SqError};
+
+use crate::cli::output;
+use crate::cli::output::Format;
+use crate::report;
+use crate::session;
+
+/// Arguments naming an `AllocationRule`, with what they're called on the
+/// command line.
+const RULES: [(&str, AllocationRule); 3] = [
+    ("weights", AllocationRule::Weights),
+    ("fixed", AllocationRule::Fixed),
+    ("waterfall", AllocationRule::Waterfall),
+];
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    let shares = |name, help| {
+        Arg::with_name(name)
+            .help(help)
+            .long(name)
+            .value_name("queue=value")
+            .takes_value(true)
+            .multiple(true)
+            .use_delimiter(true)
+            .requires("amount")
+    };
+
+    App::new("income")
+        .about("Share one income between several queues. Without options, shows how it's shared.")
+        .arg(
+            Arg::with_name("amount")
+                .help("Amount of money to share by interval")
+                .short("a")
+                .long("amount")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .help("Interval of the shared income, measured in days.")
+                .short("i")
+                .long("interval")
+                .takes_value(true)
+                .default_value("30"),
+        )
+        .arg(shares(
+            "weights",
+            "Split it in proportion to these weights, ie books=2,games=1",
+        ))
+        .arg(shares(
+            "fixed",
+            "Give each queue a fixed amount per interval, ie books=20,games=10",
+        ))
+        .arg(shares(
+            "waterfall",
+            "Fill the queues in order, each up to a balance of its value, ie savings=500,fun=100",
+        ))
+        .arg(
+            Arg::with_name("off")
+                .help("Stop sharing, every queue keeps the income it has now")
+                .long("off"),
+        )
+        .group(ArgGroup::with_name("rule").args(&["weights", "fixed", "waterfall", "off"]))
+}
+
+pub fn cmd_income(matches: &clap::ArgMatches) -> Result<()> {
+    if matches.is_present("off") {
+        return change(None);
+    }
+    let Some((name, rule)) = RULES.iter().find(|(name, _)| matches.is_present(name)) else {
+        if matches.is_present("amount") {
+            return Err(SqError::Validation(
+                "Say how to share the income, with --weights, --fixed or --waterfall.".to_string(),
+            ));
+        }
+        return show();
+    };
+
+    let amount = matches
+        .value_of("amount")
+        .unwrap()
+        .parse()
+        .map_err(|e| SqError::Parse(format!("Can't parse amount: {e}")))?;
+    let interval_in_days = matches
+        .value_of("interval")
+        .unwrap()
+        .parse()
+        .map_err(|e| SqError::Parse(format!("Can't parse interval: {e}")))?;
+    let shares = matches
+        .values_of(name)
+        .unwrap()
+        .map(parse_share)
+        .collect::<Result<Vec<Share>>>()?;
+
+    change(Some(SharedIncome {
+        income: Income {
+            amount,
+            interval_in_days,
+        },
+        rule: *rule,
+        shares,
+    }))
+}
+
+fn change(shared: Option<SharedIncome>) -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("income", |state| {
+        engine::share_income(state, shared, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+/// A share given as `queue=value`.
+fn parse_share(input: &str) -> Result<Share> {
+    let parse_error = || {
+        SqError::Parse(format!(
+            "Can't parse {input:?}, expected a queue and a number, ie books=2."
+        ))
+    };
+    let (queue, value) = input.rsplit_once('=').ok_or_else(parse_error)?;
+    Ok(Share {
+        queue: queue.trim().to_string(),
+        value: value
+            .trim()
+            .trim_start_matches('$')
+            .parse()
+            .map_err(|_| parse_error())?,
+    })
+}
+
+/// What the queue `name` gets from the shared income, for people, or `None`
+/// if it has its own.
+pub fn describe_share(state: &State, name: &str) -> Option<String> {
+    let rule = state.shared_income.as_ref()?.rule;
+    let share = state.share(name)?;
+    Some(match rule {
+        AllocationRule::Weights => format!("weight {} of the shared income", share.value),
+        AllocationRule::Fixed => format!("${:.2} of the shared income", share.value),
+        AllocationRule::Waterfall => {
+            format!("filled up to ${:.2} from the shared income", share.value)
+        }
+    })
+}
+
+/// What the queue `name` gets from the shared income, for scripts.
+pub fn share_record(state: &State, name: &str) -> Value {
+    match (&state.shared_income, state.share(name)) {
+        (Some(shared), Some(share)) => json!({ "rule": shared.rule, "value": share.value }),
+        _ => Value::Null,
+    }
+}
+
+fn show() -> Result<()> {
+    let state = session()?.load()?;
+    if output::format() == Format::Json {
+        let record = state.shared_income.as_ref().map(output::shared_income);
+        output::print_record(&json!(record), &[]);
+        return Ok(());
+    }
+    if !output::is_text() {
+        let records: Vec<Value> = state
+            .shared_income
+            .iter()
+            .flat_map(|shared| &shared.shares)
+            .map(|share| {
+                let mut record = share_record(&state, &share.queue);
+                record["queue"] = json!(share.queue);
+                record
+            })
+            .collect();
+        output::print_records(&records, &["queue", "rule", "value"]);
+        return Ok(());
+    }
+
+    let Some(shared) = &state.shared_income else {
+        println!("Every queue has an income of its own. See `sq income --help` to share one.");
+        return Ok(());
+    };
+    println!(
+        "Sharing ${:.2} per {} days, by {}:",
+        shared.income.amount, shared.income.interval_in_days, shared.rule
+    );
+    let mut table = Table::new();
+    table.set_titles(row!("Queue", "Share", "Income now"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    for share in &shared.shares {
+        let value = match shared.rule {
+            AllocationRule::Weights => share.value.to_string(),
+            AllocationRule::Fixed => format!("${:.2}", share.value),
+            AllocationRule::Waterfall => format!("up to ${:.2}", share.value),
+        };
+        let income = state.queue(&share.queue)?.income.clone();
+        let income = format!("${:.2} / {} days", income.amount, income.interval_in_days);
+        table.add_row(row!(b->share.queue, r->value, r->income));
+    }
+    table.printstd();
+    println!();
+    Ok(())
+}
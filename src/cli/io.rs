@@ -0,0 +1,101 @@
+use std::io::Error;
+use std::io::ErrorKind;
+use std::process::Command;
+
+use sq::types::M;
+use sq::Result;
+use sq::SqError;
+
+/// Read a single line from stdin, failing if stdin has been closed. A prompt
+/// that can never be answered would otherwise loop forever.
+fn next_line() -> Result<String> {
+    let mut buf = String::new();
+    match std::io::stdin().read_line(&mut buf)? {
+        0 => Err(SqError::Io(Error::new(
+            ErrorKind::UnexpectedEof,
+            "stdin was closed while waiting for an answer",
+        ))),
+        _ => Ok(buf.trim().to_string()),
+    }
+}
+
+/// Ask for a line of input. Prompts go to stderr, so they don't end up in
+/// output meant for scripts.
+pub fn read_stdin_line(prompt: &str) -> Result<String> {
+    eprintln!("{prompt}");
+    next_line()
+}
+
+/// Ask for a new value, showing the `current` one. Answering with an empty
+/// line keeps it.
+pub fn read_stdin_line_or(prompt: &str, current: &str) -> Result<String> {
+    eprintln!("{prompt} [{current}]");
+    let line = next_line()?;
+    Ok(if line.is_empty() {
+        current.to_string()
+    } else {
+        line
+    })
+}
+
+pub fn yes_no_predicate(prompt: &str) -> Result<bool> {
+    loop {
+        match read_stdin_line(&format!("{prompt} (y/n)"))?.as_ref() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            &_ => eprintln!("Please enter either yes/y or no/n"),
+        }
+    }
+}
+
+/// Ask for an amount of money until the answer is one, see `parse_money`.
+pub fn parse_money_from_stdin(prompt: &str) -> Result<M> {
+    loop {
+        eprintln!("{prompt}");
+        match parse_money(&next_line()?, "amount") {
+            Ok(amount) => {
+                return Ok(amount);
+            }
+            Err(e) => {
+                eprintln!("{e} Try again.");
+            }
+        }
+    }
+}
+
+/// Parse an amount of money as given by the user, ie `12.50` or `$12.50`,
+/// see `check_money`. `what` names it in errors, ie `price`.
+pub fn parse_money(input: &str, what: &str) -> Result<M> {
+    let amount = input
+        .trim()
+        .trim_start_matches('$')
+        .parse::<f64>()
+        .map_err(|e| SqError::Parse(format!("Can't parse {what}: {e}.")))?;
+    check_money(amount, what)
+}
+
+/// An amount of money has to be a finite number, zero or more.
+pub fn check_money(amount: f64, what: &str) -> Result<M> {
+    if amount.is_finite() && amount >= 0.0 {
+        Ok(M::from(amount))
+    } else {
+        Err(SqError::Parse(format!(
+            "The {what} has to be a number of at least zero, not {amount}."
+        )))
+    }
+}
+
+pub fn open_url(url: Option<&str>) -> Result<()> {
+    if let Some(purchase_url) = url {
+        match Command::new("open").arg(purchase_url).output() {
+            Ok(_) => Ok(()), // Everything worked as intended.
+            Err(e) => Err(SqError::Io(Error::new(
+                e.kind(),
+                format!("Can't open purchase URL: {e}"),
+            ))),
+        }
+    } else {
+        eprintln!("Would open purchase link, none present.");
+        Ok(())
+    }
+}
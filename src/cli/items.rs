@@ -0,0 +1,500 @@
+use std::io::IsTerminal;
+use std::io::Read;
+
+use clap::{App, AppSettings, Arg};
+use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
+use prettytable::row;
+use prettytable::Table;
+use serde::Deserialize;
+use serde_json::json;
+
+use sq::engine;
+use sq::engine::ItemEdit;
+use sq::types::Item;
+use sq::types::M;
+use sq::{Result, SqError};
+
+use crate::cli::io::check_money;
+use crate::cli::io::parse_money;
+use crate::cli::io::parse_money_from_stdin;
+use crate::cli::io::read_stdin_line;
+use crate::cli::io::read_stdin_line_or;
+use crate::cli::output;
+use crate::report;
+use crate::session;
+
+/// Columns of a CSV import, in the order they're expected without a header.
+const CSV_COLUMNS: [&str; 5] = ["name", "price", "url", "tags", "notes"];
+
+fn item_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("item")
+        .help("ID or position of the item, as shown by `sq list`")
+        .required(true)
+        .takes_value(true)
+}
+
+pub fn add_subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("add")
+        .about("Add an item to the queue")
+        .setting(AppSettings::TrailingVarArg)
+        .arg(
+            Arg::with_name("prepend")
+                .long("--prepend")
+                .short("p")
+                .help("Push new item to the head of the queue instead of the last spot")
+                .takes_value(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("price")
+                .help("What it costs. Asked for if missing and stdin is a terminal.")
+                .long("price")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("url")
+                .help("Where to buy it. Asked for if missing and stdin is a terminal.")
+                .short("u")
+                .long("url")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("tag")
+                .help("Label the item, can be given more than once")
+                .short("t")
+                .long("tag")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("from")
+                .help("Add every item in this file instead, one per line as CSV or JSON. - reads stdin.")
+                .long("from")
+                .value_name("file")
+                .takes_value(true)
+                .conflicts_with_all(&["price", "url", "tag", "words"]),
+        )
+        .arg(
+            Arg::with_name("words")
+                .help("Description of thing to buy")
+                .multiple(true)
+                .required_unless("from"),
+        )
+}
+
+pub fn cmd_add(matches: &clap::ArgMatches) -> Result<()> {
+    let prepend = matches.is_present("prepend");
+    let event = if let Some(source) = matches.value_of("from") {
+        let items = read_items(source)?;
+        session()?
+            .update_selected_queue("add", |queue| Ok(engine::add_all(queue, items, prepend)))?
+    } else {
+        let item = item_from_args(matches)?;
+        session()?.update_selected_queue("add", |queue| Ok(engine::add(queue, item, prepend)))?
+    };
+    report(&event);
+    Ok(())
+}
+
+/// The item described on the command line, asking for whatever is missing if
+/// someone is around to answer.
+fn item_from_args(matches: &clap::ArgMatches) -> Result<Item> {
+    let name = matches
+        .values_of("words")
+        .unwrap()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let interactive = std::io::stdin().is_terminal();
+
+    let amount = match matches.value_of("price") {
+        Some(price) => parse_price(price)?,
+        None if interactive => parse_money_from_stdin("What does this cost?: ")?,
+        None => {
+            return Err(SqError::Validation(
+                "No price given, pass it with --price.".to_string(),
+            ))
+        }
+    };
+    let purchase_link = match matches.value_of("url") {
+        Some(url) => optional(url),
+        None if interactive => optional(&read_stdin_line(
+            "Do you have a purchase URL? (Leave empty for no)",
+        )?),
+        None => None,
+    };
+    let tags = matches
+        .values_of("tag")
+        .map(|tags| tags.map(str::to_string).collect())
+        .unwrap_or_default();
+
+    new_item(name, amount, purchase_link, tags, None)
+}
+
+fn new_item(
+    name: String,
+    amount: M,
+    purchase_link: Option<String>,
+    tags: Vec<String>,
+    notes: Option<String>,
+) -> Result<Item> {
+    if name.trim().is_empty() {
+        return Err(SqError::Validation(
+            "An item's name can't be empty.".to_string(),
+        ));
+    }
+    Ok(Item {
+        id: String::new(),
+        name,
+        amount,
+        purchase_link,
+        time_purchased: None,
+        notes,
+        tags,
+        price_history: Vec::new(),
+    })
+}
+
+/// An item as given to `sq add --from` on a JSON line.
+#[derive(Deserialize)]
+struct ImportedItem {
+    name: String,
+    price: f64,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// Read the items to add from `source`, a file or `-` for stdin, see
+/// `parse_items`.
+fn read_items(source: &str) -> Result<Vec<Item>> {
+    let input = if source == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        input
+    } else {
+        std::fs::read_to_string(source)?
+    };
+    parse_items(&input)
+}
+
+/// The items in `input`. Each line is either a JSON object or CSV, with the
+/// columns in `CSV_COLUMNS` unless a header line says otherwise. Nothing is
+/// added if any line is off.
+fn parse_items(input: &str) -> Result<Vec<Item>> {
+    let mut columns: Vec<String> = CSV_COLUMNS.iter().map(ToString::to_string).collect();
+    let mut items = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fail = |reason: String| SqError::Parse(format!("Line {}: {reason}", number + 1));
+
+        if line.starts_with('{') {
+            let imported: ImportedItem =
+                serde_json::from_str(line).map_err(|e| fail(e.to_string()))?;
+            items.push(
+                new_item(
+                    imported.name,
+                    check_money(imported.price, "price").map_err(|e| fail(e.to_string()))?,
+                    imported.url.as_deref().and_then(optional),
+                    imported.tags,
+                    imported.notes.as_deref().and_then(optional),
+                )
+                .map_err(|e| fail(e.to_string()))?,
+            );
+            continue;
+        }
+
+        let fields = split_csv(line);
+        // A header names the columns, in any order, `name` among them.
+        let header = fields.iter().any(|f| f.trim().eq_ignore_ascii_case("name"));
+        if items.is_empty() && header {
+            columns = fields.iter().map(|f| f.trim().to_lowercase()).collect();
+            if let Some(unknown) = columns.iter().find(|c| !CSV_COLUMNS.contains(&c.as_str())) {
+                return Err(fail(format!(
+                    "Unknown column {unknown}, expected some of {}.",
+                    CSV_COLUMNS.join(", ")
+                )));
+            }
+            continue;
+        }
+
+        let field = |column: &str| {
+            columns
+                .iter()
+                .position(|c| c == column)
+                .and_then(|i| fields.get(i))
+                .and_then(|f| optional(f))
+        };
+        let price = field("price").ok_or_else(|| fail("No price given.".to_string()))?;
+        let tags = field("tags")
+            .map(|tags| tags.split(';').filter_map(optional).collect())
+            .unwrap_or_default();
+        items.push(
+            new_item(
+                field("name").unwrap_or_default(),
+                parse_price(&price).map_err(|e| fail(e.to_string()))?,
+                field("url"),
+                tags,
+                field("notes"),
+            )
+            .map_err(|e| fail(e.to_string()))?,
+        );
+    }
+    Ok(items)
+}
+
+/// Split a line of CSV into its fields. Fields may be quoted to contain
+/// commas, with `""` standing for a quote inside of them.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+pub fn edit_subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("edit")
+        .about("Change an item. Without any of the options, asks for each field.")
+        .arg(item_arg())
+        .arg(
+            Arg::with_name("name")
+                .help("New name of the item")
+                .short("n")
+                .long("name")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("price")
+                .help("New price, the old one is kept in the item's price history")
+                .short("p")
+                .long("price")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("url")
+                .help("New purchase URL, empty to remove it")
+                .short("u")
+                .long("url")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("notes")
+                .help("New notes, empty to remove them")
+                .long("notes")
+                .takes_value(true),
+        )
+}
+
+pub fn show_subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("show")
+        .about("Print everything about an item, including how its price changed.")
+        .arg(item_arg())
+}
+
+pub fn cmd_edit(matches: &clap::ArgMatches) -> Result<()> {
+    let target = matches.value_of("item").unwrap();
+    let session = session()?;
+    let (id, edit) = if ["name", "price", "url", "notes"]
+        .iter()
+        .any(|arg| matches.is_present(arg))
+    {
+        let edit = ItemEdit {
+            name: matches.value_of("name").map(str::to_string),
+            amount: matches.value_of("price").map(parse_price).transpose()?,
+            purchase_link: matches.value_of("url").map(optional),
+            notes: matches.value_of("notes").map(optional),
+        };
+        (target.to_string(), edit)
+    } else {
+        // Resolved to the ID before asking, so a position still means the
+        // item that was shown if the queue changes in the meantime.
+        let queue = session.selected_queue()?;
+        let item = &queue.future_purchases[engine::find_item(&queue, target)?];
+        (item.id.clone(), ask_for_edit(item)?)
+    };
+
+    let event = session.update_selected_queue("edit", |queue| {
+        engine::edit(queue, &id, edit, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+/// Ask for every field of `item` in turn, offering the current value.
+fn ask_for_edit(item: &Item) -> Result<ItemEdit> {
+    eprintln!("Press enter to keep a value, enter - to clear the URL or notes.");
+    let name = read_stdin_line_or("Name:", &item.name)?;
+    // Compared as shown rather than parsed, so that keeping a price with more
+    // than two decimals doesn't round it.
+    let price = format!("{:#.2}", item.amount);
+    let amount = loop {
+        let answer = read_stdin_line_or("Price:", &price)?;
+        if answer == price {
+            break None;
+        }
+        match parse_price(&answer) {
+            Ok(amount) => break Some(amount),
+            Err(e) => eprintln!("{e}"),
+        }
+    };
+    let clearable = |prompt, current: &Option<String>| -> Result<Option<String>> {
+        let answer = read_stdin_line_or(prompt, current.as_deref().unwrap_or(""))?;
+        Ok(optional(if answer == "-" { "" } else { &answer }))
+    };
+    let purchase_link = clearable("Purchase URL:", &item.purchase_link)?;
+    let notes = clearable("Notes:", &item.notes)?;
+
+    Ok(ItemEdit {
+        name: Some(name),
+        amount,
+        purchase_link: Some(purchase_link),
+        notes: Some(notes),
+    })
+}
+
+fn parse_price(input: &str) -> Result<M> {
+    parse_money(input, "price")
+}
+
+/// An empty value means there is none.
+fn optional(value: &str) -> Option<String> {
+    Some(value.trim())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+/// Print an item, waiting or bought, with its price history.
+pub fn cmd_show(matches: &clap::ArgMatches) -> Result<()> {
+    let target = matches.value_of("item").unwrap();
+    let queue = session()?.selected_queue()?;
+    let item = match engine::find_item(&queue, target) {
+        Ok(index) => &queue.future_purchases[index],
+        Err(e) => queue
+            .past_purchases
+            .iter()
+            .find(|item| item.id.eq_ignore_ascii_case(target))
+            .ok_or(e)?,
+    };
+
+    if !output::is_text() {
+        let mut record = output::item(item);
+        record["purchased"] = json!(item.time_purchased);
+        record["price_history"] = item
+            .price_history
+            .iter()
+            .map(|change| {
+                json!({
+                    "time": change.time,
+                    "from": output::money(change.from),
+                    "to": output::money(change.to),
+                })
+            })
+            .collect();
+        let mut columns = output::ITEM_COLUMNS.to_vec();
+        columns.push("purchased");
+        output::print_record(&record, &columns);
+        return Ok(());
+    }
+
+    println!("{} ({})", item.name, item.id);
+    println!("Price: ${:#.2}", item.amount);
+    if let Some(link) = &item.purchase_link {
+        println!("URL: {link}");
+    }
+    if let Some(notes) = &item.notes {
+        println!("Notes: {notes}");
+    }
+    if !item.tags.is_empty() {
+        println!("Tags: {}", item.tags.join(", "));
+    }
+    if let Some(time) = &item.time_purchased {
+        println!("Bought: {time}");
+    }
+
+    if !item.price_history.is_empty() {
+        println!();
+        let mut table = Table::new();
+        table.set_titles(row!("Time", "From", "To"));
+        table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+        for change in &item.price_history {
+            let from = format!("${:#.2}", change.from);
+            let to = format!("${:#.2}", change.to);
+            table.add_row(row!(change.time, r->from, r->to));
+        }
+        table.printstd();
+    }
+    println!();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sq::types::M;
+    use sq::SqError;
+
+    use super::parse_items;
+    use super::split_csv;
+
+    #[test]
+    fn splits_quoted_commas() {
+        assert_eq!(split_csv(r#"a,"b, c",d"#), vec!["a", "b, c", "d"]);
+        assert_eq!(split_csv("a,,"), vec!["a", "", ""]);
+    }
+
+    #[test]
+    fn unescapes_doubled_quotes() {
+        let fields = split_csv(r#""say ""hi""",2"#);
+        assert_eq!(fields, vec![r#"say "hi""#, "2"]);
+    }
+
+    #[test]
+    fn reads_columns_in_header_order() {
+        let items = parse_items("Price, name,tags\n3,\"lamp, red\",home;gift\n").unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "lamp, red");
+        assert_eq!(items[0].amount, M::from(3));
+        assert_eq!(items[0].tags, vec!["home", "gift"]);
+    }
+
+    #[test]
+    fn mixes_json_and_csv() {
+        let input = "lamp,$3,,,by the window\n\n{\"name\": \"book\", \"price\": 12.5}\n";
+        let items = parse_items(input).unwrap();
+
+        let names: Vec<_> = items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, vec!["lamp", "book"]);
+        assert_eq!(items[0].notes.as_deref(), Some("by the window"));
+        assert_eq!(items[1].amount, M::from(12.5));
+    }
+
+    #[test]
+    fn adds_nothing_if_any_line_is_off() {
+        for input in [
+            "lamp,3\nbook\nchair,5",
+            "lamp,3\n{\"name\": \"book\"}",
+            "lamp,-3",
+        ] {
+            assert!(matches!(parse_items(input), Err(SqError::Parse(_))));
+        }
+        assert!(parse_items("name,colour\nlamp").is_err());
+    }
+}
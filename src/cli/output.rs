@@ -0,0 +1,279 @@
+//! Output for scripts, picked with `--format`. Every command that prints
+//! something builds it as JSON first; CSV and TSV flatten that into columns.
+//! The fields are documented in the README and only ever get added to.
+
+use std::sync::OnceLock;
+
+use fraction::Zero;
+use serde_json::json;
+use serde_json::Value;
+
+use sq::types::Item;
+use sq::types::SharedIncome;
+use sq::types::M;
+use sq::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Prose and tables for people, the default.
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+/// Names accepted by `--format`.
+pub const FORMATS: [&str; 4] = ["text", "json", "csv", "tsv"];
+
+/// Format given with `--format`.
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+pub fn set_format(name: &str) {
+    let format = match name {
+        "json" => Format::Json,
+        "csv" => Format::Csv,
+        "tsv" => Format::Tsv,
+        _ => Format::Text,
+    };
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> Format {
+    FORMAT.get().copied().unwrap_or(Format::Text)
+}
+
+/// Whether output is meant for people rather than scripts.
+pub fn is_text() -> bool {
+    format() == Format::Text
+}
+
+/// Print a single `record`. `columns` are what goes into CSV and TSV, as
+/// paths into the record, ie `next_item.price`.
+pub fn print_record(record: &Value, columns: &[&str]) {
+    match format() {
+        Format::Json => println!("{}", pretty(record)),
+        _ => print_rows(std::slice::from_ref(record), columns),
+    }
+}
+
+/// Print a list of `records`, see `print_record`.
+pub fn print_records(records: &[Value], columns: &[&str]) {
+    match format() {
+        Format::Json => println!("{}", pretty(&Value::Array(records.to_vec()))),
+        _ => print_rows(records, columns),
+    }
+}
+
+fn pretty(value: &Value) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values always serialize")
+}
+
+fn print_rows(records: &[Value], columns: &[&str]) {
+    let separator = if format() == Format::Tsv { "\t" } else { "," };
+    let line = |fields: Vec<String>| println!("{}", fields.join(separator));
+
+    line(columns.iter().map(|c| escape(c)).collect());
+    for record in records {
+        line(
+            columns
+                .iter()
+                .map(|column| {
+                    let pointer = format!("/{}", column.replace('.', "/"));
+                    escape(&field(record.pointer(&pointer).unwrap_or(&Value::Null)))
+                })
+                .collect(),
+        );
+    }
+}
+
+/// A single value as a CSV or TSV field. Lists are joined with `;`.
+fn field(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(field).collect::<Vec<_>>().join(";"),
+        other => other.to_string(),
+    }
+}
+
+fn escape(field: &str) -> String {
+    if format() == Format::Tsv {
+        field.replace(['\t', '\n'], " ")
+    } else if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// An amount of money, as a number rounded to cents.
+pub fn money(amount: M) -> Value {
+    if amount.is_zero() {
+        return json!(0.0);
+    }
+    format!("{amount:.2}")
+        .parse::<f64>()
+        .map_or(Value::Null, |amount| json!(amount))
+}
+
+/// Columns of `item` for CSV and TSV.
+pub const ITEM_COLUMNS: [&str; 6] = ["id", "name", "price", "url", "tags", "notes"];
+
+pub fn item(item: &Item) -> Value {
+    json!({
+        "id": item.id,
+        "name": item.name,
+        "price": money(item.amount),
+        "url": item.purchase_link,
+        "tags": item.tags,
+        "notes": item.notes,
+    })
+}
+
+/// The shared income, with the `value` of each share meaning what its
+/// `rule` says.
+pub fn shared_income(shared: &SharedIncome) -> Value {
+    let split: Vec<Value> = shared
+        .shares
+        .iter()
+        .map(|share| json!({ "queue": share.queue, "value": share.value }))
+        .collect();
+    json!({
+        "income": {
+            "amount": shared.income.amount,
+            "interval_in_days": shared.income.interval_in_days,
+        },
+        "rule": shared.rule,
+        "shares": split,
+    })
+}
+
+/// What an operation did, named by `event` in snake case.
+pub fn event(event: &Event) -> Value {
+    match event {
+        Event::Bought {
+            item: bought,
+            remaining,
+        } => json!({
+            "event": "bought",
+            "item": item(bought),
+            "remaining": money(*remaining),
+        }),
+        Event::Bumped {
+            item,
+            from,
+            position,
+            next,
+        } => json!({
+            "event": "bumped",
+            "item": item,
+            "from": from,
+            "position": position,
+            "next": next,
+        }),
+        Event::Added {
+            item: added,
+            prepend,
+        } => json!({
+            "event": "added",
+            "item": item(added),
+            "prepend": prepend,
+        }),
+        Event::AddedAll { items, prepend } => json!({
+            "event": "added_all",
+            "items": items.iter().map(item).collect::<Vec<_>>(),
+            "prepend": prepend,
+        }),
+        Event::Deleted(deleted) => json!({ "event": "deleted", "item": item(deleted) }),
+        Event::Moved { item, position } => {
+            json!({ "event": "moved", "item": item, "position": position })
+        }
+        Event::Edited { before, after } => json!({
+            "event": "edited",
+            "before": item(before),
+            "after": item(after),
+        }),
+        Event::Paused { until } => json!({
+            "event": "paused",
+            "until": until.map(|until| until.to_rfc2822()),
+        }),
+        Event::Unpaused => json!({ "event": "unpaused" }),
+        Event::PausedAll => json!({ "event": "paused_all" }),
+        Event::UnpausedAll => json!({ "event": "unpaused_all" }),
+        Event::IncomeChanged { old, new } => json!({
+            "event": "income_changed",
+            "old": { "amount": old.amount, "interval_in_days": old.interval_in_days },
+            "new": { "amount": new.amount, "interval_in_days": new.interval_in_days },
+        }),
+        Event::IncomeShared(shared) => json!({
+            "event": "income_shared",
+            "shared_income": shared.as_ref().map(shared_income),
+        }),
+        Event::Deposited { amount, balance } => json!({
+            "event": "deposited",
+            "amount": money(*amount),
+            "balance": money(*balance),
+        }),
+        Event::Withdrew { amount, balance } => json!({
+            "event": "withdrew",
+            "amount": money(*amount),
+            "balance": money(*balance),
+        }),
+        Event::Transferred { from, to, amount } => json!({
+            "event": "transferred",
+            "from": from,
+            "to": to,
+            "amount": money(*amount),
+        }),
+        Event::PolicyChanged { queue, policy } => json!({
+            "event": "policy_changed",
+            "queue": queue,
+            "policy": policy,
+        }),
+        Event::QueueCreated(name) => json!({ "event": "queue_created", "queue": name }),
+        Event::QueueSelected(name) => json!({ "event": "queue_selected", "queue": name }),
+        Event::QueueRenamed { from, to } => {
+            json!({ "event": "queue_renamed", "from": from, "to": to })
+        }
+        Event::QueueDeleted {
+            name,
+            balance,
+            transferred_to,
+        } => json!({
+            "event": "queue_deleted",
+            "queue": name,
+            "balance": money(*balance),
+            "transferred_to": transferred_to,
+        }),
+        Event::QueueArchived(name) => json!({ "event": "queue_archived", "queue": name }),
+        Event::QueueUnarchived(name) => json!({ "event": "queue_unarchived", "queue": name }),
+    }
+}
+
+/// Print an event, with every field as a column in CSV and TSV.
+pub fn print_event(event: &Event) {
+    let record = self::event(event);
+    let mut columns = Vec::new();
+    leaves(&record, "", &mut columns);
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+    print_record(&record, &columns);
+}
+
+/// Paths to every value in `value` that isn't an object, `event` first.
+fn leaves(value: &Value, prefix: &str, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(fields) => {
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort_by_key(|key| *key != "event");
+            for key in keys {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                leaves(&fields[key], &path, paths);
+            }
+        }
+        _ => paths.push(prefix.to_string()),
+    }
+}
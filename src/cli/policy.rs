@@ -0,0 +1,237 @@
+use ansi_term::Style;
+use chrono::Duration;
+use clap::{App, Arg};
+use fraction::Zero;
+use serde_json::json;
+
+use sq::clock::Clock;
+use sq::engine;
+use sq::types::Cap;
+use sq::types::Expiry;
+use sq::types::Overdraft;
+use sq::types::Policy;
+use sq::types::Queue;
+use sq::types::M;
+use sq::{Result, SqError};
+
+use crate::cli::output;
+use crate::report;
+use crate::session;
+
+/// Columns of a policy for CSV and TSV.
+pub const POLICY_COLUMNS: [&str; 6] = [
+    "cap.amount",
+    "cap.spill_to",
+    "expiry.after_days",
+    "expiry.sweep_to",
+    "overdraft.kind",
+    "overdraft.limit",
+];
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("policy")
+        .about("Set how much money the queue keeps and how far it goes below zero. Without options, shows the current policy.")
+        .arg(
+            Arg::with_name("cap")
+                .help("Most the balance grows to, accrual stops there")
+                .long("cap")
+                .value_name("amount")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("spill_to")
+                .help("Move whatever is over the cap to this queue, instead of stopping accrual")
+                .long("spill-to")
+                .value_name("queue")
+                .takes_value(true)
+                .requires("cap"),
+        )
+        .arg(
+            Arg::with_name("no_cap")
+                .help("Remove the cap")
+                .long("no-cap")
+                .conflicts_with("cap"),
+        )
+        .arg(
+            Arg::with_name("expire_after")
+                .help("Money kept longer than this many days is gone, spending the oldest first")
+                .long("expire-after")
+                .value_name("days")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sweep_to")
+                .help("Move expired money to this queue, instead of it being gone")
+                .long("sweep-to")
+                .value_name("queue")
+                .takes_value(true)
+                .requires("expire_after"),
+        )
+        .arg(
+            Arg::with_name("no_expiry")
+                .help("Keep money for as long as it takes")
+                .long("no-expiry")
+                .conflicts_with("expire_after"),
+        )
+        .arg(
+            Arg::with_name("overdraft")
+                .help("How far below zero `--force` may take the balance: forbid, unlimited, or an amount")
+                .long("overdraft")
+                .value_name("limit")
+                .takes_value(true),
+        )
+}
+
+pub fn cmd_policy(matches: &clap::ArgMatches) -> Result<()> {
+    let changes = ["cap", "no_cap", "expire_after", "no_expiry", "overdraft"];
+    if !changes.iter().any(|arg| matches.is_present(arg)) {
+        return show();
+    }
+
+    let session = session()?;
+    let event = session.update_state("policy", |state| {
+        let name = session.queue_name(state).to_string();
+        let mut policy = state.queue(&name)?.policy.clone();
+        if matches.is_present("no_cap") {
+            policy.cap = None;
+        }
+        if let Some(amount) = matches.value_of("cap") {
+            policy.cap = Some(Cap {
+                amount: amount
+                    .trim_start_matches('$')
+                    .parse()
+                    .map_err(|e| SqError::Parse(format!("Can't parse cap: {e}")))?,
+                spill_to: matches.value_of("spill_to").map(str::to_string),
+            });
+        }
+        if matches.is_present("no_expiry") {
+            policy.expiry = None;
+        }
+        if let Some(days) = matches.value_of("expire_after") {
+            policy.expiry = Some(Expiry {
+                after_days: days
+                    .parse()
+                    .map_err(|e| SqError::Parse(format!("Can't parse days: {e}")))?,
+                sweep_to: matches.value_of("sweep_to").map(str::to_string),
+            });
+        }
+        if let Some(overdraft) = matches.value_of("overdraft") {
+            policy.overdraft = parse_overdraft(overdraft)?;
+        }
+        engine::set_policy(state, &name, policy, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+fn parse_overdraft(input: &str) -> Result<Overdraft> {
+    match input {
+        "forbid" => Ok(Overdraft::Forbid),
+        "unlimited" => Ok(Overdraft::Unlimited),
+        limit => limit
+            .trim_start_matches('$')
+            .parse()
+            .map(|limit| Overdraft::UpTo { limit })
+            .map_err(|_| {
+                SqError::Parse(format!(
+                    "Can't parse overdraft {input:?}, expected forbid, unlimited or an amount."
+                ))
+            }),
+    }
+}
+
+fn show() -> Result<()> {
+    let session = session()?;
+    let queue = session.selected_queue()?;
+    if !output::is_text() {
+        let mut record = json!(queue.policy);
+        record["queue"] = json!(queue.name);
+        let mut columns = vec!["queue"];
+        columns.extend(POLICY_COLUMNS);
+        output::print_record(&record, &columns);
+        return Ok(());
+    }
+
+    println!(
+        "Policy of queue {}:",
+        Style::new().bold().paint(&queue.name)
+    );
+    for line in describe(&queue.policy) {
+        println!("{line}");
+    }
+    println!();
+    Ok(())
+}
+
+/// The policy in a few sentences, for people.
+pub fn describe(policy: &Policy) -> Vec<String> {
+    let cap = match &policy.cap {
+        Some(Cap {
+            amount,
+            spill_to: Some(target),
+        }) => format!("Capped at ${amount:.2}, anything over it spills into {target}."),
+        Some(Cap { amount, .. }) => format!("Capped at ${amount:.2}, accrual stops there."),
+        None => "No cap, the balance grows for as long as nothing is bought.".to_string(),
+    };
+    let expiry = match &policy.expiry {
+        Some(Expiry {
+            after_days,
+            sweep_to: Some(target),
+        }) => format!("Money kept longer than {after_days} days is swept into {target}."),
+        Some(Expiry { after_days, .. }) => {
+            format!("Money kept longer than {after_days} days is gone, so spend it.")
+        }
+        None => "Money doesn't expire.".to_string(),
+    };
+    let overdraft = match policy.overdraft {
+        Overdraft::Forbid => {
+            "The balance never goes below zero, not even with --force.".to_string()
+        }
+        Overdraft::UpTo { limit } => {
+            format!("With --force, the balance goes down to ${:.2}.", -limit)
+        }
+        Overdraft::Unlimited => {
+            "With --force, the balance goes as far below zero as it takes.".to_string()
+        }
+    };
+    vec![cap, expiry, overdraft]
+}
+
+/// Warn when the balance is close to its cap, or some of it expires within
+/// the next week.
+pub fn print_warnings(queue: &Queue, balance: M, clock: &dyn Clock) -> Result<()> {
+    let italic = Style::new().italic();
+    if let Some(cap) = &queue.policy.cap {
+        let limit = M::from(cap.amount);
+        if balance * M::from(10) >= limit * M::from(9) {
+            let reached = if balance >= limit {
+                "reached"
+            } else {
+                "is close to"
+            };
+            let then = match &cap.spill_to {
+                Some(target) => format!("anything over it goes to {target}"),
+                None => "accrual stops there".to_string(),
+            };
+            println!(
+                "{}",
+                italic.paint(format!(
+                    "The balance {reached} its cap of ${limit:#.2}, {then}."
+                ))
+            );
+        }
+    }
+    if let Some(expiry) = &queue.policy.expiry {
+        let expiring = engine::expiring(queue, clock.now() + Duration::days(7), clock)?;
+        if !expiring.is_zero() {
+            let message = match &expiry.sweep_to {
+                Some(target) => {
+                    format!("${expiring:#.2} of it gets swept into {target} within a week.")
+                }
+                None => format!("${expiring:#.2} of it expires within a week, unless it's spent."),
+            };
+            println!("{}", italic.paint(message));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use clap::{App, Arg};
+use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
+use prettytable::row;
+use prettytable::Table;
+use serde_json::json;
+
+use sq::engine;
+use sq::{Result, SqError};
+
+use crate::cli::io::yes_no_predicate;
+use crate::cli::output;
+use crate::report;
+use crate::session;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    let name = |help| {
+        Arg::with_name("name")
+            .help(help)
+            .required(true)
+            .takes_value(true)
+    };
+
+    App::new("queue")
+        .about("Manage various queues and their settings")
+        .subcommand(
+            App::new("new").about("Create a new queue").arg(
+                Arg::with_name("name")
+                    .help("What to name the new queue, ie 'books'")
+                    .short("n")
+                    .long("name")
+                    .required(false)
+                    .takes_value(true),
+            ),
+        )
+        .subcommand(
+            App::new("select")
+                .about("Select a queue as active")
+                .arg(name("Queue to select")),
+        )
+        .subcommand(App::new("list").about("List all queues with their balance and next item"))
+        .subcommand(
+            App::new("rename")
+                .about("Give a queue a new name")
+                .arg(name("Queue to rename"))
+                .arg(
+                    Arg::with_name("new_name")
+                        .help("What to call it from now on")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("delete")
+                .about("Delete a queue, its items and its history")
+                .arg(name("Queue to delete"))
+                .arg(
+                    Arg::with_name("transfer_to")
+                        .help(
+                            "Move what's left of the balance to this queue, instead of dropping it",
+                        )
+                        .short("t")
+                        .long("transfer-to")
+                        .value_name("queue")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("yes")
+                        .help("Don't ask for confirmation")
+                        .short("y")
+                        .long("yes")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            App::new("archive")
+                .about("Put a queue away, it stops accruing until unarchived")
+                .arg(name("Queue to archive")),
+        )
+        .subcommand(
+            App::new("unarchive")
+                .about("Take a queue back out of the archive")
+                .arg(name("Queue to unarchive")),
+        )
+}
+
+pub fn cmd_queue(matches: &clap::ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("new", Some(m)) => {
+            let name = m.value_of("name").ok_or_else(|| {
+                SqError::Validation("A new queue needs a name, pass it with --name.".to_string())
+            })?;
+            cmd_queue_create(name)
+        }
+        ("select", Some(m)) => cmd_queue_select(m.value_of("name").unwrap()),
+        ("list", _) => cmd_queue_list(),
+        ("rename", Some(m)) => {
+            cmd_queue_rename(m.value_of("name").unwrap(), m.value_of("new_name").unwrap())
+        }
+        ("delete", Some(m)) => cmd_queue_delete(
+            m.value_of("name").unwrap(),
+            m.value_of("transfer_to"),
+            m.is_present("yes"),
+        ),
+        ("archive", Some(m)) => cmd_queue_archive(m.value_of("name").unwrap()),
+        ("unarchive", Some(m)) => cmd_queue_unarchive(m.value_of("name").unwrap()),
+        _ => {
+            eprintln!("{}", matches.usage());
+            Ok(())
+        }
+    }
+}
+
+pub fn cmd_queue_create(name: &str) -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("queue new", |state| {
+        engine::create_queue(state, name, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+pub fn cmd_queue_select(name: &str) -> Result<()> {
+    let event =
+        session()?.update_state("queue select", |state| engine::select_queue(state, name))?;
+    report(&event);
+    Ok(())
+}
+
+/// Columns of `cmd_queue_list` for CSV and TSV.
+const QUEUE_COLUMNS: [&str; 10] = [
+    "name",
+    "selected",
+    "archived",
+    "balance",
+    "income.amount",
+    "income.interval_in_days",
+    "items",
+    "next_item.id",
+    "next_item.name",
+    "next_item.price",
+];
+
+/// Print every queue with its balance, income and next item. The selected one
+/// is marked with a `*`.
+pub fn cmd_queue_list() -> Result<()> {
+    let session = session()?;
+    let state = session.load()?;
+    if !output::is_text() {
+        let mut records = Vec::new();
+        for queue in &state.queues {
+            let (_, balance) = engine::calculate_current_amount(queue, session.clock())?;
+            records.push(json!({
+                "name": queue.name,
+                "selected": queue.name == state.currently_selected,
+                "archived": queue.archived,
+                "balance": output::money(balance),
+                "income": {
+                    "amount": queue.income.amount,
+                    "interval_in_days": queue.income.interval_in_days,
+                },
+                "items": queue.future_purchases.len(),
+                "next_item": queue.future_purchases.front().map(output::item),
+            }));
+        }
+        output::print_records(&records, &QUEUE_COLUMNS);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_titles(row!("", "Name", "Balance", "Income", "Next item"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    for queue in &state.queues {
+        let (_, balance) = engine::calculate_current_amount(queue, session.clock())?;
+        let selected = if queue.name == state.currently_selected {
+            "*"
+        } else {
+            ""
+        };
+        let balance = format!("${balance:#.2}");
+        let income = format!(
+            "${:.2} / {} days",
+            queue.income.amount, queue.income.interval_in_days
+        );
+        let next = match queue.future_purchases.front() {
+            Some(item) => format!("{} (${:#.2})", item.name, item.amount),
+            None => String::new(),
+        };
+        if queue.archived {
+            table.add_row(
+                row!(selected, i->format!("{} (archived)", queue.name), r->balance, income, next),
+            );
+        } else {
+            table.add_row(row!(selected, b->queue.name, r->balance, income, next));
+        }
+    }
+
+    table.printstd();
+    println!();
+    Ok(())
+}
+
+pub fn cmd_queue_rename(name: &str, new_name: &str) -> Result<()> {
+    let event = session()?.update_state("queue rename", |state| {
+        engine::rename_queue(state, name, new_name)
+    })?;
+    report(&event);
+    Ok(())
+}
+
+/// Delete a queue after showing what goes with it and asking for
+/// confirmation, unless `yes`.
+pub fn cmd_queue_delete(name: &str, transfer_to: Option<&str>, yes: bool) -> Result<()> {
+    let session = session()?;
+    let state = session.load()?;
+    let queue = state.queue(name)?;
+    if let Some(target) = transfer_to {
+        state.queue(target)?;
+    }
+
+    let (_, balance) = engine::calculate_current_amount(queue, session.clock())?;
+    let balance = match transfer_to {
+        Some(target) => format!("its balance of ${balance:#.2} goes to {target}"),
+        None => format!("its balance of ${balance:#.2} is dropped"),
+    };
+    let prompt = format!(
+        "Delete queue {name} with {} items and {} past purchases, {balance}?",
+        queue.future_purchases.len(),
+        queue.past_purchases.len()
+    );
+    if !yes && !yes_no_predicate(&prompt)? {
+        return Ok(());
+    }
+
+    let event = session.update_state("queue delete", |state| {
+        engine::delete_queue(state, name, transfer_to, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+pub fn cmd_queue_archive(name: &str) -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("queue archive", |state| {
+        engine::archive_queue(state, name, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+pub fn cmd_queue_unarchive(name: &str) -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("queue unarchive", |state| {
+        engine::unarchive_queue(state, name, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::convert::TryFrom;
+
+use chrono::prelude::*;
+use clap::{App, Arg};
+use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
+use prettytable::row;
+use prettytable::Table;
+use serde_json::json;
+use serde_json::Value;
+
+use sq::clock::Clock;
+use sq::clock::FakeClock;
+use sq::engine;
+use sq::engine::Simulation;
+use sq::types::Income;
+use sq::types::Item;
+use sq::types::M;
+use sq::{Result, SqError};
+
+use crate::cli::io::check_money;
+use crate::cli::output;
+use crate::cli::output::Format;
+use crate::session;
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    App::new("simulate")
+        .about("Play the queue forward with a different budget or more items, without changing anything")
+        .arg(
+            Arg::with_name("amount")
+                .help("Amount of money budgeted by interval, instead of the current one")
+                .short("a")
+                .long("amount")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("interval")
+                .help("Interval of money budget, measured in days, instead of the current one")
+                .short("i")
+                .long("interval")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("add")
+                .help("Add an item to the end of the queue, ie \"A new screen:250\". Can be given more than once.")
+                .long("add")
+                .value_name("name:price")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("months")
+                .help("How far ahead to look")
+                .short("m")
+                .long("months")
+                .takes_value(true)
+                .default_value("12"),
+        )
+}
+
+pub fn cmd_simulate(matches: &clap::ArgMatches) -> Result<()> {
+    let session = session()?;
+    let mut queue = session.selected_queue()?;
+    let clock = FakeClock::new(session.clock().now());
+
+    if matches.is_present("amount") || matches.is_present("interval") {
+        let amount = match matches.value_of("amount") {
+            Some(amount) => amount
+                .parse()
+                .map_err(|e| SqError::Parse(format!("Can't parse amount: {e}")))?,
+            None => queue.income.amount,
+        };
+        let interval_in_days = match matches.value_of("interval") {
+            Some(interval) => interval
+                .parse()
+                .map_err(|e| SqError::Parse(format!("Can't parse interval: {e}")))?,
+            None => queue.income.interval_in_days,
+        };
+        engine::set_income(
+            &mut queue,
+            Income {
+                amount,
+                interval_in_days,
+            },
+            &clock,
+        )?;
+    }
+    for added in matches.values_of("add").into_iter().flatten() {
+        engine::add(&mut queue, parse_item(added)?, false);
+    }
+
+    let months = matches
+        .value_of("months")
+        .unwrap()
+        .parse()
+        .map_err(|e| SqError::Parse(format!("Can't parse months: {e}")))?;
+    let until = months_later(clock.now(), months)?;
+    let (_, start) = engine::calculate_current_amount(&queue, &clock)?;
+    let simulation = engine::simulate(queue, until, &clock)?;
+
+    if output::is_text() {
+        print_timeline(&simulation, start, until);
+    } else {
+        print_for_scripts(&simulation, until);
+    }
+    Ok(())
+}
+
+/// Print what would be bought when, and what's left at the end.
+fn print_timeline(simulation: &Simulation, start: M, until: DateTime<Local>) {
+    println!(
+        "Starting with ${start:#.2} at ${:.2} per {} days, until {}:",
+        simulation.queue.income.amount,
+        simulation.queue.income.interval_in_days,
+        until.format("%Y-%m-%d")
+    );
+    let mut table = Table::new();
+    table.set_titles(row!("Bought", "ID", "Name", "Cost", "Remaining"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    for purchase in &simulation.purchases {
+        let cost = format!("${:#.2}", purchase.item.amount);
+        let remaining = format!("${:#.2}", purchase.remaining);
+        table.add_row(row!(
+            purchase.time.format("%Y-%m-%d"),
+            purchase.item.id,
+            b->purchase.item.name,
+            r->cost,
+            r->remaining
+        ));
+    }
+    table.printstd();
+    println!();
+
+    println!("Balance at the end: ${:#.2}", simulation.balance);
+    match simulation.queue.future_purchases.front() {
+        Some(next) => println!(
+            "{} items left in the queue, next is {} for ${:#.2}.",
+            simulation.queue.future_purchases.len(),
+            next.name,
+            next.amount
+        ),
+        None => println!("Everything in the queue would be bought."),
+    }
+}
+
+/// The whole simulation in JSON, or just the purchases in CSV and TSV.
+fn print_for_scripts(simulation: &Simulation, until: DateTime<Local>) {
+    let purchases: Vec<Value> = simulation
+        .purchases
+        .iter()
+        .map(|purchase| {
+            let mut record = output::item(&purchase.item);
+            record["time"] = json!(purchase.time.to_rfc2822());
+            record["remaining"] = output::money(purchase.remaining);
+            record
+        })
+        .collect();
+    if output::format() != Format::Json {
+        let mut columns = vec!["time"];
+        columns.extend(output::ITEM_COLUMNS);
+        columns.push("remaining");
+        output::print_records(&purchases, &columns);
+        return;
+    }
+
+    let remaining: Vec<Value> = simulation
+        .queue
+        .future_purchases
+        .iter()
+        .map(output::item)
+        .collect();
+    let record = json!({
+        "income": {
+            "amount": simulation.queue.income.amount,
+            "interval_in_days": simulation.queue.income.interval_in_days,
+        },
+        "until": until.to_rfc2822(),
+        "purchases": purchases,
+        "balance": output::money(simulation.balance),
+        "remaining_items": remaining,
+    });
+    output::print_record(&record, &[]);
+}
+
+/// An item given to `--add` as `name:price`.
+fn parse_item(input: &str) -> Result<Item> {
+    let parse_error = || {
+        SqError::Parse(format!(
+            "Can't parse {input:?}, expected a name and a price, ie \"A new screen:250\"."
+        ))
+    };
+    let (name, price) = input.rsplit_once(':').ok_or_else(parse_error)?;
+    let price: f64 = price
+        .trim()
+        .trim_start_matches('$')
+        .parse()
+        .map_err(|_| parse_error())?;
+    Ok(Item {
+        id: String::new(),
+        name: name.trim().to_string(),
+        amount: check_money(price, "price")?,
+        purchase_link: None,
+        time_purchased: None,
+        notes: None,
+        tags: Vec::new(),
+        price_history: Vec::new(),
+    })
+}
+
+/// The same time of day `months` calendar months after `start`, on the last
+/// day of the month if it's shorter.
+fn months_later(start: DateTime<Local>, months: u32) -> Result<DateTime<Local>> {
+    let too_far = || SqError::Validation(format!("Can't look {months} months ahead."));
+    let index = start.month0().checked_add(months).ok_or_else(too_far)?;
+    let year = i32::try_from(index / 12)
+        .ok()
+        .and_then(|years| start.year().checked_add(years))
+        .ok_or_else(too_far)?;
+    (1..=start.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, index % 12 + 1, day))
+        .and_then(|date| {
+            Local
+                .from_local_datetime(&date.and_time(start.time()))
+                .earliest()
+        })
+        .ok_or_else(too_far)
+}
@@ -0,0 +1,51 @@
+use std::cell::Cell;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+/// Where the engine gets the current time from. Accrual depends on nothing
+/// but the time between two calculations, so swapping the clock makes it
+/// entirely predictable.
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The actual time, as the system knows it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests and for pretending it's
+/// some other time with `--now`.
+#[derive(Debug, Clone)]
+pub struct FakeClock {
+    now: Cell<DateTime<Local>>,
+}
+
+impl FakeClock {
+    #[must_use]
+    pub fn new(now: DateTime<Local>) -> Self {
+        Self {
+            now: Cell::new(now),
+        }
+    }
+
+    pub fn set(&self, now: DateTime<Local>) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Local> {
+        self.now.get()
+    }
+}
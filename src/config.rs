@@ -0,0 +1,124 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::error::Result;
+use crate::error::SqError;
+
+/// Which storage backend keeps the state, see `storage::open`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// A single pretty-printed `state.json`.
+    #[default]
+    Json,
+    /// An embedded SQLite database, `state.db`.
+    Sqlite,
+}
+
+/// How often the state gets backed up, see `backups::Backups::take`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupInterval {
+    /// At most one backup per day, taken before the first change of the day.
+    #[default]
+    Day,
+    /// Before every change.
+    Mutation,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// How many backups to keep around, older ones are removed.
+    pub keep: usize,
+    pub every: BackupInterval,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            keep: 10,
+            every: BackupInterval::Day,
+        }
+    }
+}
+
+/// User settings, read from `config.json` next to the state, see
+/// `Location::dir`. Every field is optional, a missing file means all
+/// defaults.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub storage: Backend,
+    pub backups: BackupConfig,
+}
+
+pub fn read_config(dir: &Path) -> Result<Config> {
+    let path = dir.join("config.json");
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content)
+        .map_err(|err| SqError::Parse(format!("Can't parse config file {}: {err}", path.display())))
+}
+
+/// Where sq keeps its state.
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// A state file given explicitly, its extension picks the backend.
+    File(PathBuf),
+    /// A directory holding `config.json` and the state file.
+    Dir(PathBuf),
+}
+
+impl Location {
+    /// Directory holding the configuration and anything else sq keeps next to
+    /// the state.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        match self {
+            Location::File(path) => match path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            },
+            Location::Dir(dir) => dir,
+        }
+    }
+}
+
+/// Figure out where the state lives. The first of these that is set wins:
+///
+/// 1. `--state <path>`, passed in as `flag`
+/// 2. `$SQ_STATE`, a state file
+/// 3. `$SQ_HOME`, a directory
+/// 4. `$XDG_CONFIG_HOME/sq`
+/// 5. `$HOME/.config/sq`
+pub fn locate(flag: Option<&Path>) -> Result<Location> {
+    if let Some(path) = flag {
+        return Ok(Location::File(path.to_path_buf()));
+    }
+
+    let var = |name: &str| env::var_os(name).filter(|v| !v.is_empty());
+
+    if let Some(path) = var("SQ_STATE") {
+        Ok(Location::File(path.into()))
+    } else if let Some(dir) = var("SQ_HOME") {
+        Ok(Location::Dir(dir.into()))
+    } else if let Some(dir) = var("XDG_CONFIG_HOME") {
+        Ok(Location::Dir(PathBuf::from(dir).join("sq")))
+    } else if let Some(home) = var("HOME") {
+        Ok(Location::Dir(
+            PathBuf::from(home).join(".config").join("sq"),
+        ))
+    } else {
+        Err(SqError::Storage(
+            "Don't know where to keep the state, pass --state or set $SQ_STATE, $SQ_HOME, $XDG_CONFIG_HOME or $HOME.".to_string(),
+        ))
+    }
+}
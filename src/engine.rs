@@ -0,0 +1,1474 @@
+//! The rules of a spending queue: how money accrues and what buying, bumping
+//! and the other commands do to a queue. Operations change the queue they are
+//! given and describe what happened as an `Event`. Telling the user about it,
+//! or asking them anything, is left to the caller. The current time always
+//! comes from the `Clock` passed in, never from the system directly.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use fraction::ToPrimitive;
+use fraction::Zero;
+use rand::Rng;
+
+use crate::clock::Clock;
+use crate::clock::FakeClock;
+use crate::error::Result;
+use crate::error::SqError;
+use crate::types::new_item_id;
+use crate::types::AllocationRule;
+use crate::types::Cap;
+use crate::types::EntryKind;
+use crate::types::Income;
+use crate::types::Item;
+use crate::types::Overdraft;
+use crate::types::Pause;
+use crate::types::Policy;
+use crate::types::Queue;
+use crate::types::Share;
+use crate::types::SharedIncome;
+use crate::types::State;
+use crate::types::M;
+
+/// Something an operation did to a queue.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// `item` was bought, `remaining` is what's left of the balance.
+    Bought {
+        item: Item,
+        remaining: M,
+    },
+    /// `item` was moved back from `from` to `position`, counting from 1.
+    /// `next` is the new head.
+    Bumped {
+        item: String,
+        from: usize,
+        position: usize,
+        next: String,
+    },
+    /// `item` was put at the end of the queue, or at the head if `prepend`.
+    Added {
+        item: Item,
+        prepend: bool,
+    },
+    /// `items` were put at the end of the queue in one go, or at the head if
+    /// `prepend`.
+    AddedAll {
+        items: Vec<Item>,
+        prepend: bool,
+    },
+    /// `item` was removed from the queue without buying it.
+    Deleted(Item),
+    /// `item` was put at `position`, counting from 1.
+    Moved {
+        item: String,
+        position: usize,
+    },
+    /// An item was changed by `edit`, from `before` to `after`.
+    Edited {
+        before: Item,
+        after: Item,
+    },
+    /// Accrual stopped, until `unpause` or until `until`.
+    Paused {
+        until: Option<DateTime<Local>>,
+    },
+    Unpaused,
+    /// Accrual stopped for every queue.
+    PausedAll,
+    UnpausedAll,
+    /// The income changed from `old` to `new`.
+    IncomeChanged {
+        old: Income,
+        new: Income,
+    },
+    /// The shared income is now this, or there is none anymore.
+    IncomeShared(Option<SharedIncome>),
+    /// `amount` was put into the balance by hand, leaving it at `balance`.
+    Deposited {
+        amount: M,
+        balance: M,
+    },
+    /// `amount` was taken out of the balance by hand, leaving it at `balance`.
+    Withdrew {
+        amount: M,
+        balance: M,
+    },
+    /// `amount` was moved from the queue `from` to the queue `to`.
+    Transferred {
+        from: String,
+        to: String,
+        amount: M,
+    },
+    /// The queue `queue` has a new policy.
+    PolicyChanged {
+        queue: String,
+        policy: Policy,
+    },
+    QueueCreated(String),
+    QueueSelected(String),
+    QueueRenamed {
+        from: String,
+        to: String,
+    },
+    /// The queue `name` is gone. Its `balance` went to `transferred_to`, or
+    /// nowhere. Nothing is transferred if the balance was zero.
+    QueueDeleted {
+        name: String,
+        balance: M,
+        transferred_to: Option<String>,
+    },
+    QueueArchived(String),
+    QueueUnarchived(String),
+}
+
+/// The balance as of now, including what accrued since the last time it was
+/// settled. Nothing accrues during the queue's pauses, or beyond a cap that
+/// doesn't spill over. A clock that is behind the last calculation doesn't
+/// move it back, so the time in between isn't paid out twice.
+pub fn calculate_current_amount(queue: &Queue, clock: &dyn Clock) -> Result<(DateTime<Local>, M)> {
+    let then = parse_timestamp(&queue.last_calculation, "last calculation")?;
+    let now = clock.now().max(then);
+    let time_between = accruing_seconds(queue, then, now)?;
+    let time_between = M::from(time_between);
+    let since_last_calc = time_between * money_per_second(&queue.income);
+
+    let mut subtotal = queue.current_balance + since_last_calc;
+    if let Some(cap) = accrual_cap(queue).filter(|cap| subtotal > *cap) {
+        subtotal = if queue.current_balance > cap {
+            queue.current_balance
+        } else {
+            cap
+        };
+    }
+
+    Ok((now, subtotal))
+}
+
+/// The cap accrual stops at, if there is one that doesn't spill over.
+fn accrual_cap(queue: &Queue) -> Option<M> {
+    match &queue.policy.cap {
+        Some(Cap {
+            amount,
+            spill_to: None,
+        }) => Some(M::from(*amount)),
+        _ => None,
+    }
+}
+
+/// The most the balance grows to by accruing, if the policy limits it: the
+/// cap, or what accrues before it expires.
+#[must_use]
+pub fn balance_limit(queue: &Queue) -> Option<M> {
+    let cap = queue.policy.cap.as_ref().map(|cap| M::from(cap.amount));
+    let expiry = queue.policy.expiry.as_ref().map(|expiry| {
+        money_per_second(&queue.income) * M::from(u64::from(expiry.after_days) * 24 * 60 * 60)
+    });
+    match (cap, expiry) {
+        (Some(cap), Some(expiry)) => Some(if cap < expiry { cap } else { expiry }),
+        (cap, expiry) => cap.or(expiry),
+    }
+}
+
+fn money_per_second(income: &Income) -> M {
+    let seconds_in_interval = M::from(24_u64 * 60 * 60) * M::from(income.interval_in_days);
+    M::from(income.amount) / seconds_in_interval
+}
+
+/// When an item becomes affordable, see `forecast`.
+#[derive(Debug, Clone)]
+pub struct Forecast {
+    pub item: Item,
+    /// `None` if that never happens at the current terms, ie because the
+    /// queue is paused until further notice.
+    pub affordable_at: Option<DateTime<Local>>,
+}
+
+/// When each item in the queue becomes affordable, buying them strictly in
+/// order as soon as the balance allows. Income accrues at its current rate,
+/// starting once the pauses the queue is in end by themselves. A negative
+/// balance has to be made up first. Items over the `balance_limit` never
+/// become affordable by waiting.
+pub fn forecast(queue: &Queue, clock: &dyn Clock) -> Result<Vec<Forecast>> {
+    let (now, mut balance) = calculate_current_amount(queue, clock)?;
+    let per_second = money_per_second(&queue.income);
+    let limit = balance_limit(queue);
+    let resumes = accrual_resumes(queue, now)?;
+
+    let mut at = Some(now);
+    let mut forecasts = Vec::new();
+    for item in &queue.future_purchases {
+        if let Some(time) = at.filter(|_| balance < item.amount) {
+            let reachable = limit.is_none_or(|limit| item.amount <= limit);
+            at = resumes.filter(|_| reachable).and_then(|resumes| {
+                let missing = item.amount - balance;
+                let seconds = (missing / per_second).ceil().to_i64()?;
+                balance += M::from(seconds) * per_second;
+                seconds_after(time.max(resumes), seconds)
+            });
+        }
+        if at.is_some() {
+            balance -= item.amount;
+        }
+        forecasts.push(Forecast {
+            item: item.clone(),
+            affordable_at: at,
+        });
+    }
+    Ok(forecasts)
+}
+
+/// `seconds` after `time`, or `None` if that's too far out to tell, ie for
+/// a huge price at a tiny income.
+fn seconds_after(time: DateTime<Local>, seconds: i64) -> Option<DateTime<Local>> {
+    // `Duration::seconds` panics on anything that doesn't fit in milliseconds.
+    if seconds > i64::MAX / 1000 {
+        return None;
+    }
+    time.checked_add_signed(Duration::seconds(seconds))
+}
+
+/// When money starts coming in again after `now`, once the pauses the queue
+/// is in end by themselves. `None` if it doesn't at the current terms.
+fn accrual_resumes(queue: &Queue, now: DateTime<Local>) -> Result<Option<DateTime<Local>>> {
+    if money_per_second(&queue.income) <= M::from(0) {
+        return Ok(None);
+    }
+    let mut resumes = Some(now);
+    for pause in current_pauses(queue, now)? {
+        resumes = match (resumes, pause_bounds(pause)?.1) {
+            (Some(resumes), Some(end)) => Some(resumes.max(end)),
+            _ => None,
+        };
+    }
+    Ok(resumes)
+}
+
+/// What a negative balance costs, see `debt`.
+#[derive(Debug, Clone)]
+pub struct Debt {
+    pub amount: M,
+    /// When the balance is back at zero, `None` if that doesn't happen at the
+    /// current terms.
+    pub repaid_at: Option<DateTime<Local>>,
+    /// The items that become affordable later than they would without it.
+    pub delayed: Vec<Item>,
+}
+
+/// How far the balance is below zero, when income pays that off and which
+/// items have to wait longer because of it. `None` while it isn't negative.
+pub fn debt(queue: &Queue, clock: &dyn Clock) -> Result<Option<Debt>> {
+    let (now, balance) = calculate_current_amount(queue, clock)?;
+    if balance >= M::from(0) {
+        return Ok(None);
+    }
+    let amount = -balance;
+
+    let repaid_at = accrual_resumes(queue, now)?.and_then(|resumes| {
+        let seconds = (amount / money_per_second(&queue.income)).ceil().to_i64()?;
+        seconds_after(resumes, seconds)
+    });
+
+    let mut debt_free = queue.clone();
+    update_accumulation(&mut debt_free, clock)?;
+    debt_free.current_balance = M::from(0);
+    let delayed = forecast(queue, clock)?
+        .into_iter()
+        .zip(forecast(&debt_free, clock)?)
+        .filter(|(with, without)| with.affordable_at != without.affordable_at)
+        .map(|(with, _)| with.item)
+        .collect();
+
+    Ok(Some(Debt {
+        amount,
+        repaid_at,
+        delayed,
+    }))
+}
+
+/// How many of the seconds between `from` and `to` the queue spent unpaused.
+/// A queue paused on its own while everything was paused only counts once.
+/// None at all if `to` isn't after `from`.
+fn accruing_seconds(queue: &Queue, from: DateTime<Local>, to: DateTime<Local>) -> Result<i64> {
+    if to <= from {
+        return Ok(0);
+    }
+    let mut paused = Vec::new();
+    for pause in &queue.pauses {
+        let (start, end) = pause_bounds(pause)?;
+        let start = start.max(from);
+        let end = end.map_or(to, |end| end.min(to));
+        if end > start {
+            paused.push((start, end));
+        }
+    }
+    paused.sort();
+
+    let mut seconds = to.signed_duration_since(from).num_seconds();
+    let mut counted_until = from;
+    for (start, end) in paused {
+        let start = start.max(counted_until);
+        if end > start {
+            seconds -= end.signed_duration_since(start).num_seconds();
+            counted_until = end;
+        }
+    }
+    Ok(seconds)
+}
+
+/// The pauses the queue is in at `now`. There are at most two, one of its
+/// own and one from pausing everything.
+pub fn current_pauses(queue: &Queue, now: DateTime<Local>) -> Result<Vec<&Pause>> {
+    let mut current = Vec::new();
+    for pause in &queue.pauses {
+        let (start, end) = pause_bounds(pause)?;
+        if start <= now && end.is_none_or(|end| now < end) {
+            current.push(pause);
+        }
+    }
+    Ok(current)
+}
+
+/// Index of the pause the queue is in at `now`, either of its own or from
+/// pausing everything, depending on `global`.
+fn current_pause_index(queue: &Queue, now: DateTime<Local>, global: bool) -> Result<Option<usize>> {
+    for (i, pause) in queue.pauses.iter().enumerate() {
+        let (start, end) = pause_bounds(pause)?;
+        if pause.global == global && start <= now && end.is_none_or(|end| now < end) {
+            return Ok(Some(i));
+        }
+    }
+    Ok(None)
+}
+
+fn pause_bounds(pause: &Pause) -> Result<(DateTime<Local>, Option<DateTime<Local>>)> {
+    let start = parse_timestamp(&pause.from, "pause")?;
+    let end = match &pause.until {
+        Some(until) => Some(parse_timestamp(until, "pause")?),
+        None => None,
+    };
+    Ok((start, end))
+}
+
+fn parse_timestamp(timestamp: &str, what: &str) -> Result<DateTime<Local>> {
+    DateTime::parse_from_rfc2822(timestamp)
+        .map(|t| t.with_timezone(&Local))
+        .map_err(|e| {
+            SqError::Parse(format!(
+                "Can't parse date of {what} {timestamp:?}, check the statefile: {e}"
+            ))
+        })
+}
+
+/// Settle what accrued since the last calculation into the ledger. Has to
+/// happen before anything else touches the balance or the income, so accrual
+/// up to now is accounted at the old terms.
+pub fn update_accumulation(queue: &mut Queue, clock: &dyn Clock) -> Result<()> {
+    let then = parse_timestamp(&queue.last_calculation, "last calculation")?;
+    let (new_timestamp, new_amount) = calculate_current_amount(queue, clock)?;
+    let accrued = new_amount - queue.current_balance;
+    if !accrued.is_zero() {
+        let capped = accrual_cap(queue)
+            .filter(|cap| new_amount >= *cap)
+            .map_or(String::new(), |cap| {
+                format!(", up to the cap of ${cap:#.2}")
+            });
+        let reason = format!(
+            "Income of ${:.2} per {} days, since {}{capped}",
+            queue.income.amount, queue.income.interval_in_days, queue.last_calculation
+        );
+        queue.record_accrual((then, new_timestamp), accrued, reason);
+    }
+    queue.last_calculation = new_timestamp.to_rfc2822();
+    Ok(())
+}
+
+/// An item bought by `simulate`, at `time`, leaving `remaining`.
+#[derive(Debug, Clone)]
+pub struct SimulatedPurchase {
+    pub time: DateTime<Local>,
+    pub item: Item,
+    pub remaining: M,
+}
+
+/// What `simulate` predicts.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub purchases: Vec<SimulatedPurchase>,
+    /// The balance at the end.
+    pub balance: M,
+    /// The queue as it would be at the end, with whatever wasn't bought.
+    pub queue: Queue,
+}
+
+/// Play `queue` forward until `until`, buying every item in order as soon as
+/// it's affordable, see `forecast`. The queue keeps to its policy along the
+/// way, see `trim_to_policy`. Works on its own copy of the queue with a clock
+/// of its own, so nothing is changed.
+pub fn simulate(mut queue: Queue, until: DateTime<Local>, clock: &dyn Clock) -> Result<Simulation> {
+    let clock = FakeClock::new(clock.now());
+    let mut purchases = Vec::new();
+    trim_to_policy(&mut queue, &clock)?;
+    loop {
+        let next = forecast(&queue, &clock)?.into_iter().next();
+        let Some((item, at)) = next.and_then(|f| Some((f.item, f.affordable_at?))) else {
+            break;
+        };
+        if at > until {
+            break;
+        }
+        clock.set(at);
+        trim_to_policy(&mut queue, &clock)?;
+        let (_, balance) = calculate_current_amount(&queue, &clock)?;
+        if balance < item.amount {
+            // Some of the money expired on the way there.
+            continue;
+        }
+        // At `at` the balance can match the price exactly, which
+        // `check_affordable` doesn't count as enough yet.
+        if let Event::Bought { item, remaining } =
+            buy(&mut queue, &item.id, item.amount, true, &clock)?
+        {
+            purchases.push(SimulatedPurchase {
+                time: at,
+                item,
+                remaining,
+            });
+        }
+    }
+
+    clock.set(until.max(clock.now()));
+    trim_to_policy(&mut queue, &clock)?;
+    let (_, balance) = calculate_current_amount(&queue, &clock)?;
+    Ok(Simulation {
+        purchases,
+        balance,
+        queue,
+    })
+}
+
+/// Take away what the policy of `queue` doesn't let it keep as of now: money
+/// over the cap and money past its expiry. Where it spills over or is swept
+/// to doesn't matter here, `simulate` only plays the one queue forward, see
+/// `enforce_policies` for the real thing.
+fn trim_to_policy(queue: &mut Queue, clock: &dyn Clock) -> Result<()> {
+    let policy = queue.policy.clone();
+    if let Some(cap) = &policy.cap {
+        let (_, balance) = calculate_current_amount(queue, clock)?;
+        let limit = M::from(cap.amount);
+        if balance > limit {
+            let (kind, reason) = match &cap.spill_to {
+                Some(target) => (
+                    EntryKind::Transfer,
+                    format!("Over its cap of ${limit:#.2}, spilled over into {target}"),
+                ),
+                None => (
+                    EntryKind::Adjustment,
+                    format!("Over its cap of ${limit:#.2}"),
+                ),
+            };
+            update_accumulation(queue, clock)?;
+            queue.record(clock.now(), kind, limit - balance, reason);
+        }
+    }
+
+    if let Some(expiry) = &policy.expiry {
+        let expired = expiring(queue, clock.now(), clock)?;
+        if !expired.is_zero() {
+            let days = expiry.after_days;
+            let (kind, reason) = match &expiry.sweep_to {
+                Some(target) => (
+                    EntryKind::Transfer,
+                    format!("Kept longer than {days} days, swept into {target}"),
+                ),
+                None => (EntryKind::Expiry, format!("Kept longer than {days} days")),
+            };
+            update_accumulation(queue, clock)?;
+            queue.record(clock.now(), kind, -expired, reason);
+        }
+    }
+    Ok(())
+}
+
+/// Find the item `target` refers to, either by its ID or by its 1-based
+/// position in the queue. Returns its index in `future_purchases`.
+pub fn find_item(queue: &Queue, target: &str) -> Result<usize> {
+    let found = match target.parse::<usize>() {
+        Ok(position) => position
+            .checked_sub(1)
+            .filter(|&i| i < queue.future_purchases.len()),
+        Err(_) => queue
+            .future_purchases
+            .iter()
+            .position(|item| item.id.eq_ignore_ascii_case(target)),
+    };
+    found.ok_or_else(|| SqError::MissingItem(target.to_string()))
+}
+
+/// The item `target` refers to, or the head of the queue without a target.
+pub fn target_item<'a>(queue: &'a Queue, target: Option<&str>) -> Result<&'a Item> {
+    match target {
+        Some(target) => Ok(&queue.future_purchases[find_item(queue, target)?]),
+        None => queue
+            .future_purchases
+            .front()
+            .ok_or_else(|| SqError::EmptyQueue("No item in the queue to act on.".to_string())),
+    }
+}
+
+/// Whether there's enough money to spend `cost` right now. With `force`, the
+/// balance may end up below zero, as far as the overdraft policy allows.
+pub fn check_affordable(queue: &Queue, cost: M, force: bool, clock: &dyn Clock) -> Result<()> {
+    let (_, balance) = calculate_current_amount(queue, clock)?;
+    if cost < balance {
+        Ok(())
+    } else if force {
+        check_overdraft_limit(queue, balance - cost)
+    } else {
+        Err(SqError::Validation(
+            "Can't buy item, not enough money accumulated.".to_string(),
+        ))
+    }
+}
+
+/// Whether the overdraft policy allows the balance to end up at `remaining`.
+fn check_overdraft_limit(queue: &Queue, remaining: M) -> Result<()> {
+    if remaining >= M::from(0) {
+        return Ok(());
+    }
+    match queue.policy.overdraft {
+        Overdraft::Forbid => Err(SqError::Validation(format!(
+            "Queue {} doesn't allow going below zero.",
+            queue.name
+        ))),
+        Overdraft::UpTo { limit } if -remaining > M::from(limit) => {
+            Err(SqError::Validation(format!(
+                "That would leave queue {} ${:#.2} below zero, more than its overdraft limit of ${limit:.2}.",
+                queue.name, -remaining
+            )))
+        }
+        Overdraft::UpTo { .. } | Overdraft::Unlimited => Ok(()),
+    }
+}
+
+/// Buy the item with the ID `id` for `cost`. Usually that's the head of the
+/// queue, but any item can be bought out of order.
+pub fn buy(queue: &mut Queue, id: &str, cost: M, force: bool, clock: &dyn Clock) -> Result<Event> {
+    let Some(index) = queue.future_purchases.iter().position(|item| item.id == id) else {
+        return Err(SqError::Validation(
+            "The item is gone from the queue, nothing was bought.".to_string(),
+        ));
+    };
+    update_accumulation(queue, clock)?;
+    check_affordable(queue, cost, force, clock)?;
+    Ok(purchase(queue, index, cost, clock.now()))
+}
+
+fn purchase(queue: &mut Queue, index: usize, cost: M, now: DateTime<Local>) -> Event {
+    let mut item = queue
+        .future_purchases
+        .remove(index)
+        .expect("Callers look up the index first");
+
+    item.time_purchased = Some(now.to_rfc2822());
+    item.set_price(cost, now);
+    let kind = if cost > queue.current_balance {
+        EntryKind::Overdraft
+    } else {
+        EntryKind::Purchase
+    };
+
+    queue.record(now, kind, -cost, format!("Bought {}", item.name));
+    queue.past_purchases.push_back(item.clone());
+
+    Event::Bought {
+        item,
+        remaining: queue.current_balance,
+    }
+}
+
+/// Move an item, the head of the queue unless `target` says otherwise, back a
+/// random number of spots. This is essentially a "not right now" button for
+/// reordering the queue.
+pub fn bump<R: Rng + ?Sized>(
+    queue: &mut Queue,
+    target: Option<&str>,
+    rng: &mut R,
+) -> Result<Event> {
+    let index = match target {
+        Some(target) => find_item(queue, target)?,
+        None => 0,
+    };
+    let new_index = match queue.future_purchases.len() {
+        0 => {
+            return Err(SqError::EmptyQueue(
+                "No items in the queue, can't bump anything.".to_string(),
+            ))
+        }
+        1 => {
+            return Err(SqError::Validation(
+                "One item in the queue, can't bump anything.".to_string(),
+            ))
+        }
+        len if index == len - 1 => {
+            return Err(SqError::Validation(
+                "The item is last in the queue already, can't bump it.".to_string(),
+            ))
+        }
+        // Never to the very end, unless that's the only way back. The x..=y
+        // syntax is an inclusive range, the x..y syntax by default is
+        // *exclusive*, meaning that a range like 1..1 is empty, and causes
+        // `rand` to panic.
+        len => rng.gen_range(index + 1..=(len - 2).max(index + 1)),
+    };
+
+    move_within(queue, index, new_index);
+    let item = queue.future_purchases[new_index].name.clone();
+    let next = queue.future_purchases[0].name.clone();
+
+    Ok(Event::Bumped {
+        item,
+        from: index + 1,
+        position: new_index + 1,
+        next,
+    })
+}
+
+/// Put the item `target` refers to at `position`, counting from 1.
+pub fn move_item(queue: &mut Queue, target: &str, position: usize) -> Result<Event> {
+    let index = find_item(queue, target)?;
+    let len = queue.future_purchases.len();
+    if position == 0 || position > len {
+        return Err(SqError::Validation(format!(
+            "Can't move to position {position}, the queue has {len} items."
+        )));
+    }
+
+    move_within(queue, index, position - 1);
+    Ok(Event::Moved {
+        item: queue.future_purchases[position - 1].name.clone(),
+        position,
+    })
+}
+
+/// Move the item at `from` to `to`, shifting everything in between by one.
+fn move_within(queue: &mut Queue, from: usize, to: usize) {
+    let items = queue.future_purchases.make_contiguous();
+    if from < to {
+        items[from..=to].rotate_left(1);
+    } else {
+        items[to..=from].rotate_right(1);
+    }
+}
+
+/// Put `item` in the queue. It gets a fresh ID, whatever it came with.
+pub fn add(queue: &mut Queue, item: Item, prepend: bool) -> Event {
+    let item = insert(queue, item, prepend);
+    Event::Added { item, prepend }
+}
+
+/// Put every one of `items` in the queue, keeping them in the order given.
+/// Like `add`, each of them gets a fresh ID.
+pub fn add_all(queue: &mut Queue, mut items: Vec<Item>, prepend: bool) -> Event {
+    // Prepending one by one reverses them, so start with the last one.
+    if prepend {
+        items.reverse();
+    }
+    let mut added: Vec<Item> = items
+        .into_iter()
+        .map(|item| insert(queue, item, prepend))
+        .collect();
+    if prepend {
+        added.reverse();
+    }
+    Event::AddedAll {
+        items: added,
+        prepend,
+    }
+}
+
+fn insert(queue: &mut Queue, mut item: Item, prepend: bool) -> Item {
+    item.id = new_item_id(&queue.item_ids());
+    if prepend {
+        queue.future_purchases.push_front(item.clone());
+    } else {
+        queue.future_purchases.push_back(item.clone());
+    }
+    item
+}
+
+/// Changes to make to an item. Fields left at `None` stay as they are, the
+/// link and notes can be cleared with `Some(None)`.
+#[derive(Debug, Clone, Default)]
+pub struct ItemEdit {
+    pub name: Option<String>,
+    pub amount: Option<M>,
+    pub purchase_link: Option<Option<String>>,
+    pub notes: Option<Option<String>>,
+}
+
+/// Apply `edit` to the item `target` refers to. A new price is added to the
+/// item's price history.
+pub fn edit(queue: &mut Queue, target: &str, edit: ItemEdit, clock: &dyn Clock) -> Result<Event> {
+    if edit
+        .name
+        .as_deref()
+        .is_some_and(|name| name.trim().is_empty())
+    {
+        return Err(SqError::Validation(
+            "An item's name can't be empty.".to_string(),
+        ));
+    }
+    let index = find_item(queue, target)?;
+    let item = &mut queue.future_purchases[index];
+    let before = item.clone();
+
+    if let Some(name) = edit.name {
+        item.name = name;
+    }
+    if let Some(amount) = edit.amount {
+        item.set_price(amount, clock.now());
+    }
+    if let Some(purchase_link) = edit.purchase_link {
+        item.purchase_link = purchase_link;
+    }
+    if let Some(notes) = edit.notes {
+        item.notes = notes;
+    }
+
+    Ok(Event::Edited {
+        before,
+        after: item.clone(),
+    })
+}
+
+/// Remove an item without buying it, the head of the queue unless `target`
+/// says otherwise.
+pub fn delete(queue: &mut Queue, target: Option<&str>) -> Result<Event> {
+    let index = match target {
+        Some(target) => find_item(queue, target)?,
+        None => 0,
+    };
+    queue
+        .future_purchases
+        .remove(index)
+        .map(Event::Deleted)
+        .ok_or_else(|| SqError::EmptyQueue("No item in queue, can't remove any.".to_string()))
+}
+
+/// Stop accrual from now on, either until `unpause` or, if given, until
+/// `until`. Pausing a paused queue changes when it resumes.
+pub fn pause(
+    queue: &mut Queue,
+    until: Option<DateTime<Local>>,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    let now = clock.now();
+    if until.is_some_and(|until| until <= now) {
+        return Err(SqError::Validation(
+            "Can't pause until a time that has already passed.".to_string(),
+        ));
+    }
+    update_accumulation(queue, clock)?;
+
+    let until_stamp = until.map(|until| until.to_rfc2822());
+    match current_pause_index(queue, now, false)? {
+        Some(i) => queue.pauses[i].until = until_stamp,
+        None => queue.pauses.push(Pause {
+            from: now.to_rfc2822(),
+            until: until_stamp,
+            global: false,
+        }),
+    }
+    Ok(Event::Paused { until })
+}
+
+/// Resume accrual right away, ending the current pause early if it has an
+/// end date. A queue that is only paused because everything is stays paused.
+pub fn unpause(queue: &mut Queue, clock: &dyn Clock) -> Result<Event> {
+    let now = clock.now();
+    let Some(i) = current_pause_index(queue, now, false)? else {
+        return Err(SqError::Validation(
+            if current_pause_index(queue, now, true)?.is_some() {
+                "The queue is only paused because everything is, run `sq unpause --all`."
+            } else {
+                "The queue isn't paused."
+            }
+            .to_string(),
+        ));
+    };
+    update_accumulation(queue, clock)?;
+    queue.pauses[i].until = Some(now.to_rfc2822());
+    Ok(Event::Unpaused)
+}
+
+/// Stop accrual for every queue until `unpause_all`. Each queue gets a pause
+/// of its own, marked as global, so accrual doesn't need to look beyond the
+/// queue. Queues that were already paused stay paused after `unpause_all`.
+pub fn pause_all(state: &mut State, clock: &dyn Clock) -> Result<Event> {
+    if state.globally_paused {
+        return Err(SqError::Validation(
+            "Everything is paused already.".to_string(),
+        ));
+    }
+    for queue in &mut state.queues {
+        update_accumulation(queue, clock)?;
+        queue.pauses.push(global_pause(clock.now()));
+    }
+    state.globally_paused = true;
+    Ok(Event::PausedAll)
+}
+
+pub fn unpause_all(state: &mut State, clock: &dyn Clock) -> Result<Event> {
+    if !state.globally_paused {
+        return Err(SqError::Validation(
+            "Nothing is paused globally.".to_string(),
+        ));
+    }
+    let now = clock.now();
+    for queue in &mut state.queues {
+        if let Some(i) = current_pause_index(queue, now, true)? {
+            update_accumulation(queue, clock)?;
+            queue.pauses[i].until = Some(now.to_rfc2822());
+        }
+    }
+    state.globally_paused = false;
+    Ok(Event::UnpausedAll)
+}
+
+/// The pause a queue gets while everything is paused, ie when it's created
+/// in the middle of it.
+#[must_use]
+pub fn global_pause(now: DateTime<Local>) -> Pause {
+    Pause {
+        from: now.to_rfc2822(),
+        until: None,
+        global: true,
+    }
+}
+
+/// Change the income, settling what accrued at the old one first.
+pub fn set_income(queue: &mut Queue, income: Income, clock: &dyn Clock) -> Result<Event> {
+    validate_income(&income)?;
+    update_accumulation(queue, clock)?;
+    let reason = format!(
+        "Income changed from ${:.2} per {} days to ${:.2} per {} days",
+        queue.income.amount, queue.income.interval_in_days, income.amount, income.interval_in_days
+    );
+    queue.record(clock.now(), EntryKind::BudgetChange, M::from(0), reason);
+    let old = std::mem::replace(&mut queue.income, income.clone());
+    Ok(Event::IncomeChanged { old, new: income })
+}
+
+/// Put `amount` into the balance, ie a gift card or a birthday present.
+/// `note` says where it came from.
+pub fn deposit(
+    queue: &mut Queue,
+    amount: M,
+    note: Option<&str>,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    check_positive(amount)?;
+    update_accumulation(queue, clock)?;
+    let reason = with_note("Deposited by hand", note);
+    queue.record(clock.now(), EntryKind::Deposit, amount, reason);
+    Ok(Event::Deposited {
+        amount,
+        balance: queue.current_balance,
+    })
+}
+
+/// Take `amount` out of the balance, ie for something bought outside of the
+/// queue. `note` says what for. With `force`, the balance may end up below
+/// zero, see `check_affordable`.
+pub fn withdraw(
+    queue: &mut Queue,
+    amount: M,
+    note: Option<&str>,
+    force: bool,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    check_positive(amount)?;
+    update_accumulation(queue, clock)?;
+    check_overdraft(queue, amount, force)?;
+    let reason = with_note("Withdrew by hand", note);
+    queue.record(clock.now(), EntryKind::Withdrawal, -amount, reason);
+    Ok(Event::Withdrew {
+        amount,
+        balance: queue.current_balance,
+    })
+}
+
+/// Move `amount` from the queue `from` to the queue `to`, recorded in both
+/// ledgers. With `force`, `from` may end up below zero, see `withdraw`.
+pub fn transfer(
+    state: &mut State,
+    from: &str,
+    to: &str,
+    amount: M,
+    note: Option<&str>,
+    force: bool,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    if from == to {
+        return Err(SqError::Validation(
+            "Can't transfer from a queue to itself.".to_string(),
+        ));
+    }
+    check_positive(amount)?;
+    state.queue(to)?;
+
+    let source = state.queue_mut(from)?;
+    update_accumulation(source, clock)?;
+    check_overdraft(source, amount, force)?;
+    let reasons = (
+        with_note(&format!("Transferred to {to}"), note),
+        with_note(&format!("Transferred from {from}"), note),
+    );
+    move_money(state, from, to, amount, reasons, clock)?;
+
+    Ok(Event::Transferred {
+        from: from.to_string(),
+        to: to.to_string(),
+        amount,
+    })
+}
+
+/// Take `amount` out of `from` and put it into `to`, with the `reasons` for
+/// each side in their ledgers.
+fn move_money(
+    state: &mut State,
+    from: &str,
+    to: &str,
+    amount: M,
+    reasons: (String, String),
+    clock: &dyn Clock,
+) -> Result<()> {
+    let source = state.queue_mut(from)?;
+    update_accumulation(source, clock)?;
+    source.record(clock.now(), EntryKind::Transfer, -amount, reasons.0);
+    let target = state.queue_mut(to)?;
+    update_accumulation(target, clock)?;
+    target.record(clock.now(), EntryKind::Transfer, amount, reasons.1);
+    Ok(())
+}
+
+fn check_positive(amount: M) -> Result<()> {
+    if amount > M::from(0) {
+        Ok(())
+    } else {
+        Err(SqError::Validation(
+            "The amount has to be more than zero.".to_string(),
+        ))
+    }
+}
+
+/// Whether `amount` can be taken out of the settled balance without it going
+/// below zero, or with `force`, without going further than the overdraft
+/// policy allows.
+fn check_overdraft(queue: &Queue, amount: M, force: bool) -> Result<()> {
+    if amount <= queue.current_balance {
+        Ok(())
+    } else if force {
+        check_overdraft_limit(queue, queue.current_balance - amount)
+    } else {
+        Err(SqError::Validation(format!(
+            "Can't take out ${amount:#.2}, the balance of queue {} is ${:#.2}.",
+            queue.name, queue.current_balance
+        )))
+    }
+}
+
+/// A ledger reason, with the `note` given by the user after it.
+fn with_note(reason: &str, note: Option<&str>) -> String {
+    match note.map(str::trim).filter(|note| !note.is_empty()) {
+        Some(note) => format!("{reason}: {note}"),
+        None => reason.to_string(),
+    }
+}
+
+/// Add an empty queue called `name`. It starts out paused if everything is.
+pub fn create_queue(state: &mut State, name: &str, clock: &dyn Clock) -> Result<Event> {
+    let name = validate_name(state, name)?;
+    let mut queue = Queue::new(name.clone(), clock.now());
+    if state.globally_paused {
+        queue.pauses.push(global_pause(clock.now()));
+    }
+    state.queues.push(queue);
+    Ok(Event::QueueCreated(name))
+}
+
+/// Share one income between several queues from now on, or stop sharing with
+/// `None`. In that case every queue keeps the income it had last.
+pub fn share_income(
+    state: &mut State,
+    shared: Option<SharedIncome>,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    if let Some(shared) = &shared {
+        validate_shares(state, shared)?;
+    }
+    for queue in &mut state.queues {
+        update_accumulation(queue, clock)?;
+    }
+    state.shared_income.clone_from(&shared);
+    allocate(state, clock)?;
+    Ok(Event::IncomeShared(shared))
+}
+
+/// An income needs an interval of at least a day to spread over, and an
+/// amount that is an actual number, zero or more.
+fn validate_income(income: &Income) -> Result<()> {
+    if income.interval_in_days == 0 {
+        return Err(SqError::Validation(
+            "The interval has to be at least one day.".to_string(),
+        ));
+    }
+    if !income.amount.is_finite() || income.amount < 0.0 {
+        return Err(SqError::Validation(format!(
+            "The income has to be a number of at least zero, not {}.",
+            income.amount
+        )));
+    }
+    Ok(())
+}
+
+fn validate_shares(state: &State, shared: &SharedIncome) -> Result<()> {
+    validate_income(&shared.income)?;
+    if shared.shares.is_empty() {
+        return Err(SqError::Validation(
+            "A shared income needs at least one queue to go to.".to_string(),
+        ));
+    }
+    for (i, share) in shared.shares.iter().enumerate() {
+        state.queue(&share.queue)?;
+        if shared.shares[..i].iter().any(|s| s.queue == share.queue) {
+            return Err(SqError::Validation(format!(
+                "Queue {} is in the shared income twice.",
+                share.queue
+            )));
+        }
+        if !share.value.is_finite() || share.value <= 0.0 {
+            return Err(SqError::Validation(format!(
+                "The share of queue {} has to be a number more than zero.",
+                share.queue
+            )));
+        }
+    }
+
+    let fixed: f64 = shared.shares.iter().map(|share| share.value).sum();
+    if shared.rule == AllocationRule::Fixed && fixed > shared.income.amount {
+        return Err(SqError::Validation(format!(
+            "The fixed amounts add up to ${fixed:.2}, more than the ${:.2} there is to share.",
+            shared.income.amount
+        )));
+    }
+    Ok(())
+}
+
+/// Give every queue with a share of the shared income its part of it, as
+/// its income. For a waterfall, whatever is above a queue's cap spills over
+/// into the next queue first, and the first queue below its cap gets all of
+/// the income. Needs to happen whenever balances change, so the income goes
+/// where it should from then on.
+pub fn allocate(state: &mut State, clock: &dyn Clock) -> Result<()> {
+    let Some(shared) = state.shared_income.clone() else {
+        return Ok(());
+    };
+
+    let amounts: Vec<f64> = match shared.rule {
+        AllocationRule::Weights => {
+            let total: f64 = shared.shares.iter().map(|share| share.value).sum();
+            shared
+                .shares
+                .iter()
+                .map(|share| shared.income.amount * share.value / total)
+                .collect()
+        }
+        AllocationRule::Fixed => shared.shares.iter().map(|share| share.value).collect(),
+        AllocationRule::Waterfall => {
+            spill(state, &shared.shares, clock)?;
+            let mut filling = shared.shares.len() - 1;
+            for (i, share) in shared.shares.iter().enumerate() {
+                let (_, balance) = calculate_current_amount(state.queue(&share.queue)?, clock)?;
+                if balance < M::from(share.value) {
+                    filling = i;
+                    break;
+                }
+            }
+            (0..shared.shares.len())
+                .map(|i| {
+                    if i == filling {
+                        shared.income.amount
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        }
+    };
+
+    for (share, amount) in shared.shares.iter().zip(amounts) {
+        let income = Income {
+            amount,
+            interval_in_days: shared.income.interval_in_days,
+        };
+        let queue = state.queue_mut(&share.queue)?;
+        if queue.income != income {
+            set_income(queue, income, clock)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move whatever is above each queue's cap into the next queue, in order, so
+/// it can go on from there.
+fn spill(state: &mut State, shares: &[Share], clock: &dyn Clock) -> Result<()> {
+    for pair in shares.windows(2) {
+        let (share, next) = (&pair[0], &pair[1]);
+        let cap = M::from(share.value);
+        let queue = state.queue_mut(&share.queue)?;
+        if calculate_current_amount(queue, clock)?.1 <= cap {
+            continue;
+        }
+
+        update_accumulation(queue, clock)?;
+        let over = queue.current_balance - cap;
+        queue.record(
+            clock.now(),
+            EntryKind::Adjustment,
+            -over,
+            format!(
+                "Over its cap of ${:.2}, spilled over into {}",
+                share.value, next.queue
+            ),
+        );
+        let next_queue = state.queue_mut(&next.queue)?;
+        update_accumulation(next_queue, clock)?;
+        next_queue.record(
+            clock.now(),
+            EntryKind::Adjustment,
+            over,
+            format!("Spilled over from {}", share.queue),
+        );
+    }
+    Ok(())
+}
+
+/// Give the queue `name` a new policy, replacing the old one. Takes effect
+/// from now on, what accrued until now is settled at the old terms.
+pub fn set_policy(
+    state: &mut State,
+    name: &str,
+    policy: Policy,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    if policy
+        .cap
+        .as_ref()
+        .is_some_and(|cap| !cap.amount.is_finite() || cap.amount <= 0.0)
+    {
+        return Err(SqError::Validation(
+            "The cap has to be more than zero.".to_string(),
+        ));
+    }
+    if matches!(policy.overdraft, Overdraft::UpTo { limit } if !limit.is_finite() || limit < 0.0) {
+        return Err(SqError::Validation(
+            "The overdraft limit can't be below zero.".to_string(),
+        ));
+    }
+    if policy
+        .expiry
+        .as_ref()
+        .is_some_and(|expiry| expiry.after_days == 0)
+    {
+        return Err(SqError::Validation(
+            "Money has to be kept for at least a day before it expires.".to_string(),
+        ));
+    }
+    for target in policy.targets() {
+        if target == name {
+            return Err(SqError::Validation(format!(
+                "Queue {name} can't pass money on to itself."
+            )));
+        }
+        state.queue(target)?;
+    }
+
+    let queue = state.queue_mut(name)?;
+    update_accumulation(queue, clock)?;
+    queue.policy = policy.clone();
+    Ok(Event::PolicyChanged {
+        queue: name.to_string(),
+        policy,
+    })
+}
+
+/// Apply every queue's policy as of now. Whatever is over a cap that spills
+/// over moves to the queue it spills into, money past its expiry is gone or
+/// swept into another queue. Caps that don't spill over are taken care of as
+/// money accrues, see `calculate_current_amount`.
+pub fn enforce_policies(state: &mut State, clock: &dyn Clock) -> Result<()> {
+    for index in 0..state.queues.len() {
+        let queue = &state.queues[index];
+        let name = queue.name.clone();
+        let policy = queue.policy.clone();
+
+        if let Some(Cap {
+            amount,
+            spill_to: Some(target),
+        }) = &policy.cap
+        {
+            let (_, balance) = calculate_current_amount(queue, clock)?;
+            let cap = M::from(*amount);
+            if balance > cap {
+                let reasons = (
+                    format!("Over its cap of ${cap:#.2}, spilled over into {target}"),
+                    format!("Spilled over from {name}"),
+                );
+                move_money(state, &name, target, balance - cap, reasons, clock)?;
+            }
+        }
+
+        let Some(expiry) = &policy.expiry else {
+            continue;
+        };
+        let expired = expiring(&state.queues[index], clock.now(), clock)?;
+        if expired.is_zero() {
+            continue;
+        }
+        let days = expiry.after_days;
+        if let Some(target) = &expiry.sweep_to {
+            let reasons = (
+                format!("Kept longer than {days} days, swept into {target}"),
+                format!("Swept over from {name}"),
+            );
+            move_money(state, &name, target, expired, reasons, clock)?;
+        } else {
+            let queue = &mut state.queues[index];
+            update_accumulation(queue, clock)?;
+            let reason = format!("Kept longer than {days} days");
+            queue.record(clock.now(), EntryKind::Expiry, -expired, reason);
+        }
+    }
+    Ok(())
+}
+
+/// How much of the balance will have been kept longer than the expiry at
+/// `at`, spending the oldest money first. Income counts from when it accrued,
+/// evenly over the time it took, anything else from when it came in.
+pub fn expiring(queue: &Queue, at: DateTime<Local>, clock: &dyn Clock) -> Result<M> {
+    let Some(expiry) = &queue.policy.expiry else {
+        return Ok(M::from(0));
+    };
+    let Some(cutoff) = at.checked_sub_signed(Duration::days(i64::from(expiry.after_days))) else {
+        return Ok(M::from(0));
+    };
+
+    let then = parse_timestamp(&queue.last_calculation, "last calculation")?;
+    let (now, balance) = calculate_current_amount(queue, clock)?;
+    let pending = balance - queue.current_balance;
+    let mut recent = accrued_after(queue, pending, (then, now), cutoff)?;
+    for entry in queue.ledger.iter().rev() {
+        let time = parse_timestamp(&entry.time, "ledger entry")?;
+        if time <= cutoff {
+            break;
+        }
+        if entry.amount > M::from(0) {
+            recent += match &entry.since {
+                Some(since) => {
+                    let since = parse_timestamp(since, "ledger entry")?;
+                    accrued_after(queue, entry.amount, (since, time), cutoff)?
+                }
+                None => entry.amount,
+            };
+        }
+    }
+    Ok(if balance > recent {
+        balance - recent
+    } else {
+        M::from(0)
+    })
+}
+
+/// How much of `amount`, accrued evenly over the unpaused time between `from`
+/// and `to`, came in after `cutoff`.
+fn accrued_after(
+    queue: &Queue,
+    amount: M,
+    (from, to): (DateTime<Local>, DateTime<Local>),
+    cutoff: DateTime<Local>,
+) -> Result<M> {
+    if cutoff <= from {
+        return Ok(amount);
+    }
+    let total = accruing_seconds(queue, from, to)?;
+    if cutoff >= to || total == 0 {
+        return Ok(M::from(0));
+    }
+    let after = accruing_seconds(queue, cutoff, to)?;
+    Ok(amount * M::from(after) / M::from(total))
+}
+
+/// Make `name` the queue commands act on.
+pub fn select_queue(state: &mut State, name: &str) -> Result<Event> {
+    if state.queue(name)?.archived {
+        return Err(SqError::Validation(format!(
+            "Queue {name} is archived, unarchive it first."
+        )));
+    }
+    state.currently_selected = name.to_string();
+    Ok(Event::QueueSelected(name.to_string()))
+}
+
+pub fn rename_queue(state: &mut State, from: &str, to: &str) -> Result<Event> {
+    state.queue(from)?;
+    let to = validate_name(state, to)?;
+    state.queue_mut(from)?.name.clone_from(&to);
+    if state.currently_selected == from {
+        state.currently_selected.clone_from(&to);
+    }
+    if let Some(shared) = &mut state.shared_income {
+        for share in shared.shares.iter_mut().filter(|share| share.queue == from) {
+            share.queue.clone_from(&to);
+        }
+    }
+    for policy in state.queues.iter_mut().map(|queue| &mut queue.policy) {
+        let spill_to = policy.cap.as_mut().and_then(|cap| cap.spill_to.as_mut());
+        let sweep_to = policy
+            .expiry
+            .as_mut()
+            .and_then(|expiry| expiry.sweep_to.as_mut());
+        for target in spill_to
+            .into_iter()
+            .chain(sweep_to)
+            .filter(|target| *target == from)
+        {
+            target.clone_from(&to);
+        }
+    }
+    Ok(Event::QueueRenamed {
+        from: from.to_string(),
+        to,
+    })
+}
+
+/// Remove the queue `name` along with its items and history. What's left of
+/// its balance goes to `transfer_to` if given, and is dropped otherwise. If
+/// it was selected, the first queue that isn't archived takes its place.
+pub fn delete_queue(
+    state: &mut State,
+    name: &str,
+    transfer_to: Option<&str>,
+    clock: &dyn Clock,
+) -> Result<Event> {
+    if transfer_to == Some(name) {
+        return Err(SqError::Validation(
+            "Can't transfer the balance of a queue to itself.".to_string(),
+        ));
+    }
+    if let Some(source) = state
+        .queues
+        .iter()
+        .find(|queue| queue.name != name && queue.policy.targets().any(|target| target == name))
+    {
+        return Err(SqError::Validation(format!(
+            "Queue {} passes money on to {name}, change its policy first.",
+            source.name
+        )));
+    }
+
+    let queue = state.queue_mut(name)?;
+    update_accumulation(queue, clock)?;
+    let balance = queue.current_balance;
+
+    if let Some(target) = transfer_to {
+        state.queue(target)?;
+    }
+    if let (Some(target), false) = (transfer_to, balance.is_zero()) {
+        let from = state.queue_mut(name)?;
+        from.record(
+            clock.now(),
+            EntryKind::Adjustment,
+            -balance,
+            format!("Moved to {target}, deleting this queue"),
+        );
+        let to = state.queue_mut(target)?;
+        update_accumulation(to, clock)?;
+        to.record(
+            clock.now(),
+            EntryKind::Adjustment,
+            balance,
+            format!("Moved over from {name}, which was deleted"),
+        );
+    }
+
+    if state.currently_selected == name {
+        let Some(next) = state.queues.iter().find(|q| q.name != name && !q.archived) else {
+            return Err(SqError::Validation(format!(
+                "Queue {name} is the only one that isn't archived, can't delete it."
+            )));
+        };
+        state.currently_selected = next.name.clone();
+    }
+    state.queues.retain(|q| q.name != name);
+    if let Some(shared) = &mut state.shared_income {
+        shared.shares.retain(|share| share.queue != name);
+        if shared.shares.is_empty() {
+            state.shared_income = None;
+        }
+    }
+
+    Ok(Event::QueueDeleted {
+        name: name.to_string(),
+        balance,
+        transferred_to: transfer_to
+            .filter(|_| !balance.is_zero())
+            .map(str::to_string),
+    })
+}
+
+/// Put a queue away: it stays around with its balance and history, but stops
+/// accruing and can't be selected until it's unarchived.
+pub fn archive_queue(state: &mut State, name: &str, clock: &dyn Clock) -> Result<Event> {
+    if state.currently_selected == name {
+        return Err(SqError::Validation(format!(
+            "Queue {name} is selected, select another one before archiving it."
+        )));
+    }
+    let queue = state.queue_mut(name)?;
+    if queue.archived {
+        return Err(SqError::Validation(format!(
+            "Queue {name} is archived already."
+        )));
+    }
+    pause(queue, None, clock)?;
+    queue.archived = true;
+    Ok(Event::QueueArchived(name.to_string()))
+}
+
+/// Take a queue back out of the archive. It picks up accruing where it left
+/// off.
+pub fn unarchive_queue(state: &mut State, name: &str, clock: &dyn Clock) -> Result<Event> {
+    let queue = state.queue_mut(name)?;
+    if !queue.archived {
+        return Err(SqError::Validation(format!("Queue {name} isn't archived.")));
+    }
+    unpause(queue, clock)?;
+    queue.archived = false;
+    Ok(Event::QueueUnarchived(name.to_string()))
+}
+
+/// Names identify queues, so they have to be unique. Surrounding whitespace
+/// is dropped.
+fn validate_name(state: &State, name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(SqError::Validation("A queue needs a name.".to_string()));
+    }
+    if state.queues.iter().any(|q| q.name == name) {
+        return Err(SqError::Validation(format!(
+            "There already is a queue called {name}."
+        )));
+    }
+    Ok(name.to_string())
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong in sq. Each kind of failure exits with its own
+/// code, see `exit_code`, so scripts wrapping sq can tell them apart.
+#[derive(Debug)]
+pub enum SqError {
+    /// The state couldn't be located, read or written.
+    Storage(String),
+    /// Something didn't parse, ie a price that isn't a number, or a state
+    /// file that isn't valid JSON.
+    Parse(String),
+    /// The state file can't be parsed. It was left alone, a copy of it was put
+    /// aside as `quarantined`, and `backup` is the most recent backup it could
+    /// be restored from, see `recovery::restore`.
+    Corrupt {
+        path: PathBuf,
+        reason: String,
+        quarantined: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    /// The input parsed, but doesn't make sense right now, ie buying without
+    /// enough money accumulated.
+    Validation(String),
+    /// No queue with that name exists.
+    MissingQueue(String),
+    /// No item with that ID or at that position exists.
+    MissingItem(String),
+    /// The queue has no items to act on.
+    EmptyQueue(String),
+    /// Any other I/O failure, ie stdin going away in the middle of a prompt.
+    Io(io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, SqError>;
+
+impl SqError {
+    /// Process exit code for this kind of failure. Usage errors are reported
+    /// by clap with exit code 1.
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SqError::Storage(_) => 2,
+            SqError::Parse(_) | SqError::Corrupt { .. } => 3,
+            SqError::Validation(_) => 4,
+            SqError::MissingQueue(_) => 5,
+            SqError::EmptyQueue(_) => 6,
+            SqError::Io(_) => 7,
+            SqError::MissingItem(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for SqError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SqError::Storage(msg)
+            | SqError::Parse(msg)
+            | SqError::Validation(msg)
+            | SqError::EmptyQueue(msg) => f.write_str(msg),
+            SqError::Corrupt {
+                path,
+                reason,
+                quarantined,
+                ..
+            } => write!(
+                f,
+                "Can't parse state file {}: {reason}\nA copy of the unreadable file has been kept at {}",
+                path.display(),
+                quarantined.display()
+            ),
+            SqError::MissingQueue(name) => write!(f, "There is no queue called {name}."),
+            SqError::MissingItem(target) => write!(
+                f,
+                "There is no item {target} in the queue, see `sq list` for IDs and positions."
+            ),
+            SqError::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SqError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SqError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SqError {
+    fn from(err: io::Error) -> Self {
+        SqError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SqError {
+    fn from(err: serde_json::Error) -> Self {
+        SqError::Parse(format!("{:?} error, {err}", err.classify()))
+    }
+}
+
+impl From<rusqlite::Error> for SqError {
+    fn from(err: rusqlite::Error) -> Self {
+        SqError::Storage(format!("Database error: {err}"))
+    }
+}
@@ -0,0 +1,281 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::clock::Clock;
+use crate::error::Result;
+use crate::error::SqError;
+use crate::notice::Notice;
+use crate::notice::Notify;
+use crate::storage;
+use crate::storage::Storage;
+use crate::types::Queue;
+use crate::types::State;
+
+/// How many changes can be undone.
+const JOURNAL_LENGTH: usize = 20;
+
+/// The state as it was before a change, so the change can be undone.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Snapshot {
+    pub time: String,
+    /// The command that made the change, ie `delete`.
+    pub action: String,
+    pub state: State,
+}
+
+/// What it takes to turn one state into another. Most changes only touch a
+/// single queue, so only the queues that differ are kept, everything else
+/// about the state is small enough to keep whole.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Patch {
+    /// The target state, without its queues.
+    state: State,
+    /// Names of all queues of the target, in order.
+    order: Vec<String>,
+    /// Queues of the target that aren't the same in the source.
+    changed: Vec<Queue>,
+}
+
+impl Patch {
+    /// The patch that turns `from` into `to`.
+    fn between(from: &State, to: &State) -> Self {
+        let changed = to
+            .queues
+            .iter()
+            .filter(|queue| from.queues.iter().find(|q| q.name == queue.name) != Some(queue))
+            .cloned()
+            .collect();
+
+        Self {
+            state: State {
+                schema_version: to.schema_version,
+                queues: Vec::new(),
+                currently_selected: to.currently_selected.clone(),
+                globally_paused: to.globally_paused,
+                shared_income: to.shared_income.clone(),
+            },
+            order: to.queues.iter().map(|q| q.name.clone()).collect(),
+            changed,
+        }
+    }
+
+    /// Turn `from` into the target state. Fails if `from` lacks a queue the
+    /// patch expects to be there unchanged, ie because the state file was
+    /// edited by hand.
+    fn apply(self, from: &State) -> Result<State> {
+        let mut state = self.state;
+        let mut changed = self.changed;
+        for name in self.order {
+            let queue = match changed.iter().position(|q| q.name == name) {
+                Some(index) => changed.swap_remove(index),
+                None => from
+                    .queues
+                    .iter()
+                    .find(|q| q.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        SqError::Storage(format!(
+                            "Undo history doesn't match the state, queue {name} is missing."
+                        ))
+                    })?,
+            };
+            state.queues.push(queue);
+        }
+        Ok(state)
+    }
+}
+
+/// A single change in the journal, as written to disk.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Entry {
+    time: String,
+    action: String,
+    /// Turns the state after the change back into the state before it, or
+    /// for redo entries, the other way around.
+    patch: Patch,
+}
+
+/// Which way through the journal an entry goes.
+#[derive(Debug, Clone, Copy)]
+enum Side {
+    Undo,
+    Redo,
+}
+
+impl Side {
+    fn prefix(self) -> &'static str {
+        match self {
+            Side::Undo => "undo-",
+            Side::Redo => "redo-",
+        }
+    }
+}
+
+/// Bounded history of changes to the state, kept next to it in the
+/// `<state file>.journal` directory, one file per change. Undoing a change
+/// moves it over to the redo side, any new change clears that. Recording a
+/// change only writes that one change, not the whole history.
+pub struct Journal {
+    dir: PathBuf,
+}
+
+impl Journal {
+    /// The journal belonging to `store`. Everything done with it should
+    /// happen while holding the storage lock.
+    #[must_use]
+    pub fn open(store: &dyn Storage) -> Journal {
+        let mut dir = store.path().to_path_buf().into_os_string();
+        dir.push(".journal");
+        Journal {
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Remember how to get from `after` back to `before`, the state as it was
+    /// before `action` changed it. A journal written by an older sq is
+    /// dropped, `notify` hears about that.
+    pub fn record(
+        &self,
+        action: &str,
+        (before, after): (&State, &State),
+        clock: &dyn Clock,
+        notify: Notify,
+    ) -> Result<()> {
+        if self.dir.is_file() {
+            // A single file holding every snapshot, as written by older sq.
+            self.start_over("it's from an older sq", notify)?;
+        }
+        fs::create_dir_all(&self.dir)?;
+
+        self.push(
+            Side::Undo,
+            &Entry {
+                time: clock.now().to_rfc2822(),
+                action: action.to_string(),
+                patch: Patch::between(after, before),
+            },
+        )?;
+        let undo = self.entries(Side::Undo)?;
+        if undo.len() > JOURNAL_LENGTH {
+            for (_, path) in &undo[..undo.len() - JOURNAL_LENGTH] {
+                fs::remove_file(path)?;
+            }
+        }
+        for (_, path) in self.entries(Side::Redo)? {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Step back one change: save the state as it was before it into `store`
+    /// and return it. `current` is kept so the step can be redone.
+    pub fn undo(
+        &self,
+        store: &dyn Storage,
+        current: &State,
+        notify: Notify,
+    ) -> Result<Option<Snapshot>> {
+        self.step(store, current, (Side::Undo, Side::Redo), notify)
+    }
+
+    /// Step forward again after an undo, like `undo`.
+    pub fn redo(
+        &self,
+        store: &dyn Storage,
+        current: &State,
+        notify: Notify,
+    ) -> Result<Option<Snapshot>> {
+        self.step(store, current, (Side::Redo, Side::Undo), notify)
+    }
+
+    /// Take the latest entry off `from`, apply it to `current` and put the
+    /// way back onto `to`. An entry that can't be read only costs the
+    /// ability to undo, so the history is dropped and `notify` hears about it.
+    fn step(
+        &self,
+        store: &dyn Storage,
+        current: &State,
+        (from, to): (Side, Side),
+        notify: Notify,
+    ) -> Result<Option<Snapshot>> {
+        if !self.dir.is_dir() {
+            return Ok(None);
+        }
+        let Some((_, path)) = self.entries(from)?.pop() else {
+            return Ok(None);
+        };
+        let entry: Entry = match serde_json::from_str(&fs::read_to_string(&path)?) {
+            Ok(entry) => entry,
+            Err(err) => {
+                self.start_over(&format!("{} can't be read: {err}", path.display()), notify)?;
+                return Ok(None);
+            }
+        };
+
+        let state = entry.patch.apply(current)?;
+        store.save(&state)?;
+        self.push(
+            to,
+            &Entry {
+                time: entry.time.clone(),
+                action: entry.action.clone(),
+                patch: Patch::between(&state, current),
+            },
+        )?;
+        fs::remove_file(&path)?;
+
+        Ok(Some(Snapshot {
+            time: entry.time,
+            action: entry.action,
+            state,
+        }))
+    }
+
+    /// Write `entry` as the latest one on `side`.
+    fn push(&self, side: Side, entry: &Entry) -> Result<()> {
+        let next = self.entries(side)?.last().map_or(0, |(n, _)| n + 1);
+        let path = self.dir.join(format!("{}{next:06}.json", side.prefix()));
+        storage::write_atomically(&path, &serde_json::to_string(entry)?)
+    }
+
+    /// Files of the entries on `side`, oldest first.
+    fn entries(&self, side: Side) -> Result<Vec<(u64, PathBuf)>> {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(n) = sequence_number(&path, side) {
+                found.push((n, path));
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    fn start_over(&self, reason: &str, notify: Notify) -> Result<()> {
+        notify(&Notice::JournalDropped {
+            path: self.dir.clone(),
+            reason: reason.to_string(),
+        });
+        if self.dir.is_dir() {
+            fs::remove_dir_all(&self.dir)?;
+        } else {
+            fs::remove_file(&self.dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Position of the entry at `path` on `side`, ie 3 for `undo-000003.json`.
+fn sequence_number(path: &Path, side: Side) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(side.prefix())?
+        .strip_suffix(".json")?
+        .parse()
+        .ok()
+}
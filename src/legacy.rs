@@ -1,50 +1,39 @@
-use std::collections::VecDeque;
-use std::io::Result;
-
 use serde::Deserialize;
-use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
 
-use crate::config_file_path;
-use crate::types::Income;
-use crate::types::Item;
-use crate::types::Queue;
-use crate::types::State;
-use crate::types::M;
-use crate::write_file;
+use crate::error::Result;
 
-/// Attempt to read state file in the old format, and if successful, write it
-/// back in the new format. No semantic changes should be made in the migration.
-pub fn migrate_statefile() -> Result<State> {
-    let file = std::fs::read_to_string(config_file_path())?;
-    let parsed: LegacyState = serde_json::from_str(&file)?;
+/// Migrate the original single-queue state file (schema v0) to the multi-queue
+/// layout (schema v1). No semantic changes should be made in the migration, the
+/// old contents become the "default" queue.
+pub fn v0_to_v1(value: Value) -> Result<Value> {
+    let parsed: LegacyState = serde_json::from_value(value)?;
 
-    let new_state = State {
-        queues: vec![Queue {
-            income: parsed.income,
-            name: "default".into(),
-            last_calculation: parsed.last_calculation,
-            current_balance: parsed.current_amount,
-            future_purchases: parsed.future_purchases,
-            past_purchases: parsed.past_purchases,
-            paused: parsed.paused.unwrap_or(false),
+    Ok(json!({
+        "queues": [{
+            "income": parsed.income,
+            "name": "default",
+            "last_calculation": parsed.last_calculation,
+            "current_balance": parsed.current_amount,
+            "future_purchases": parsed.future_purchases,
+            "past_purchases": parsed.past_purchases,
+            "paused": parsed.paused.unwrap_or(false),
         }],
-        currently_selected: "default".into(),
-        globally_paused: false,
-    };
-
-    println!("Migrated config file to current format, continuing...");
-    write_file(&new_state)?;
-    Ok(new_state)
+        "currently_selected": "default",
+        "globally_paused": false,
+    }))
 }
 
-/// Legacy format of the state file. This is going to get interesting when I
-/// need to add another migration...
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Legacy format of the state file, from before sq knew about multiple queues.
+/// Nested values are kept opaque, they are carried over verbatim and later
+/// migrations take care of their shape.
+#[derive(Deserialize, Debug, Clone)]
 struct LegacyState {
-    income: Income,
+    income: Value,
     last_calculation: String,
-    current_amount: M,
-    future_purchases: VecDeque<Item>,
-    past_purchases: VecDeque<Item>,
+    current_amount: Value,
+    future_purchases: Value,
+    past_purchases: Value,
     paused: Option<bool>,
 }
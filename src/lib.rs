@@ -0,0 +1,43 @@
+//! The engine behind sq, the tiniest spending queue. The `sq` binary is a thin
+//! command line around it, other tools can use it to read and change the same
+//! state without going through the command line.
+//!
+//! `Session` finds the state and takes care of locking, backups and the undo
+//! journal around every change. The operations in `engine` do the actual work
+//! on a `Queue` and report what they did as `Event`s.
+//!
+//! ```no_run
+//! # fn main() -> sq::Result<()> {
+//! let session = sq::Session::open(None)?;
+//! let event = session.update_selected_queue("bump", |queue| {
+//!     sq::engine::bump(queue, None, &mut rand::thread_rng())
+//! })?;
+//! # let _ = event;
+//! # Ok(())
+//! # }
+//! ```
+#![warn(clippy::pedantic, clippy::all)]
+// Everything fallible returns an `SqError`, which documents what can go wrong.
+#![allow(clippy::missing_errors_doc)]
+pub mod backups;
+pub mod clock;
+pub mod config;
+pub mod engine;
+pub mod error;
+pub mod journal;
+mod legacy;
+mod migrations;
+pub mod notice;
+pub mod recovery;
+pub mod session;
+pub mod storage;
+pub mod types;
+
+pub use crate::engine::Event;
+pub use crate::error::Result;
+pub use crate::error::SqError;
+pub use crate::notice::Notice;
+pub use crate::session::Session;
+pub use crate::types::Item;
+pub use crate::types::Queue;
+pub use crate::types::State;
@@ -1,104 +1,265 @@
 #![warn(clippy::pedantic, clippy::all)]
-mod io;
-mod legacy;
-mod queues;
-mod types;
+mod cli;
 
 use ansi_term::Color;
 use ansi_term::Style;
-use chrono::prelude::*;
-use clap::{App, AppSettings, Arg};
-use prettytable::cell;
+use clap::{App, Arg};
+use fraction::Zero;
 use prettytable::format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR;
 use prettytable::row;
 use prettytable::Table;
-use rand::Rng;
-use std::env;
-use std::fs;
-use std::io::Error;
-use std::io::ErrorKind;
-use std::io::Result;
+use serde_json::json;
+use serde_json::Value;
+use std::io::IsTerminal;
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
-use crate::types::Income;
-use crate::types::Item;
-use crate::types::Queue;
-use crate::types::State;
-use crate::types::M;
+use chrono::prelude::*;
+use sq::clock::Clock;
+use sq::clock::FakeClock;
+use sq::engine;
+use sq::engine::calculate_current_amount;
+use sq::journal::Journal;
+use sq::journal::Snapshot;
+use sq::notice::Notify;
+use sq::recovery;
+use sq::storage::Storage;
+use sq::types::Income;
+use sq::types::Item;
+use sq::types::Queue;
+use sq::types::State;
+use sq::types::M;
+use sq::Event;
+use sq::Notice;
+use sq::Result;
+use sq::Session;
+use sq::SqError;
 
-use crate::io::open_url;
-use crate::io::parse_float_from_stdin;
-use crate::io::read_stdin_line;
-use crate::io::yes_no_predicate;
+use crate::cli::backups;
+use crate::cli::balance;
+use crate::cli::income;
+use crate::cli::io::open_url;
+use crate::cli::io::parse_money;
+use crate::cli::io::parse_money_from_stdin;
+use crate::cli::io::yes_no_predicate;
+use crate::cli::items;
+use crate::cli::output;
+use crate::cli::policy;
+use crate::cli::queues;
+use crate::cli::simulate;
 
 fn main() {
     let args = parse_args();
-    let res = match args.subcommand() {
+    let res = apply_global_flags(&args).and_then(|()| match run(&args) {
+        Err(err @ SqError::Corrupt { .. }) => recover(&err).and_then(|()| run(&args)),
+        res => res,
+    });
+
+    if let Err(e) = res {
+        let f = Style::new().bold().fg(Color::Red);
+        eprintln!("{}", f.paint(e.to_string()));
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn apply_global_flags(args: &clap::ArgMatches) -> Result<()> {
+    if let Some(path) = args.value_of_os("state") {
+        let _ = STATE_FLAG.set(PathBuf::from(path));
+    }
+    if let Some(queue) = args.value_of("queue") {
+        let _ = QUEUE_FLAG.set(queue.to_string());
+    }
+    if let Some(format) = args.value_of("format") {
+        output::set_format(format);
+    }
+    if let Some(now) = args.value_of("now") {
+        let _ = NOW_FLAG.set(parse_time(now, "--now")?);
+    }
+    Ok(())
+}
+
+/// Parse a point in time given on the command line, either as RFC 3339, RFC
+/// 2822 or just a date, meaning midnight at its start.
+fn parse_time(input: &str, flag: &str) -> Result<DateTime<Local>> {
+    if let Ok(time) =
+        DateTime::parse_from_rfc3339(input).or_else(|_| DateTime::parse_from_rfc2822(input))
+    {
+        return Ok(time.with_timezone(&Local));
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .ok_or_else(|| {
+            SqError::Parse(format!(
+                "Can't parse {flag} {input:?}, expected ie 2021-06-18 or 2021-06-18T12:00:00+02:00."
+            ))
+        })
+}
+
+fn run(args: &clap::ArgMatches) -> Result<()> {
+    match args.subcommand() {
         ("budget", Some(m)) => {
-            // This is a bug waiting to happen, I think. Needs to be converted
-            // to pass Options to `update_budget`.
-            let income = m
+            let amount = m
                 .value_of("amount")
                 .unwrap()
                 .parse()
-                .expect("can't parse amount");
-            let interval = m
+                .map_err(|e| SqError::Parse(format!("Can't parse amount: {e}")))?;
+            let interval_in_days = m
                 .value_of("interval")
                 .unwrap()
                 .parse()
-                .expect("can't parse interval");
+                .map_err(|e| SqError::Parse(format!("Can't parse interval: {e}")))?;
 
-            cmd_budget(income, interval)
+            cmd_budget(Income {
+                amount,
+                interval_in_days,
+            })
         }
         ("buy", Some(m)) => {
             let no_open = m.is_present("no_open");
             let peek = m.is_present("peek");
             let force = m.is_present("force");
+            let target = m.value_of("item");
             match m.value_of("new_price") {
                 Some(p) => {
-                    match p.parse::<f64>() {
-                        Ok(new_price) => {
-                            let price: M = new_price.into();
-                            cmd_buy(no_open, Some(price), peek, force)},
-                        Err(_) => Err(Error::new(
-                            ErrorKind::InvalidInput,
-                            "Can't parse specified price to float.\n(Did you accidentally specify `-peek` instead of `--peek`?)")),
-                    }
-                },
-                None => cmd_buy(no_open, None, peek, force),
+                    let price = parse_money(p, "price").map_err(|e| {
+                        SqError::Parse(format!(
+                            "{e}\n(Did you accidentally specify `-peek` instead of `--peek`?)"
+                        ))
+                    })?;
+                    cmd_buy(target, no_open, Some(price), peek, force)
+                }
+                None => cmd_buy(target, no_open, None, peek, force),
             }
         }
         ("list", _) => cmd_list(),
-        ("delete", _) => cmd_delete(),
+        ("delete", Some(m)) => cmd_delete(m.value_of("item")),
         ("past", _) => cmd_past(),
-        ("bump", _) => cmd_bump(),
-        ("pause", _) => cmd_pause(),
-        ("unpause", _) => cmd_unpause(),
-        ("add", Some(m)) => {
-            let prepend = m.is_present("prepend");
-            let to_add = m
-                .values_of("words")
+        ("forecast", _) => cmd_forecast(),
+        ("ledger", _) => cmd_ledger(),
+        ("undo", _) => cmd_undo(),
+        ("redo", _) => cmd_redo(),
+        ("bump", Some(m)) => cmd_bump(m.value_of("item")),
+        ("move", Some(m)) => {
+            let position = m
+                .value_of("position")
                 .unwrap()
-                .collect::<Vec<&str>>()
-                .join(" ");
-            cmd_add(to_add, prepend)
+                .parse()
+                .map_err(|e| SqError::Parse(format!("Can't parse position: {e}")))?;
+            cmd_move(m.value_of("item").unwrap(), position)
         }
+        ("pause", Some(m)) if m.is_present("all") => cmd_pause_all(),
+        ("pause", Some(m)) => match m.value_of("until") {
+            Some(until) => parse_time(until, "--until").and_then(|until| cmd_pause(Some(until))),
+            None => cmd_pause(None),
+        },
+        ("unpause", Some(m)) if m.is_present("all") => cmd_unpause_all(),
+        ("unpause", _) => cmd_unpause(),
+        ("add", Some(m)) => items::cmd_add(m),
+        ("edit", Some(m)) => items::cmd_edit(m),
+        ("show", Some(m)) => items::cmd_show(m),
+        ("deposit", Some(m)) => balance::cmd_deposit(m),
+        ("withdraw", Some(m)) => balance::cmd_withdraw(m),
+        ("transfer", Some(m)) => balance::cmd_transfer(m),
+        ("income", Some(m)) => income::cmd_income(m),
+        ("policy", Some(m)) => policy::cmd_policy(m),
+        ("simulate", Some(m)) => simulate::cmd_simulate(m),
         ("queue", Some(m)) => queues::cmd_queue(m),
-        _ => cmd_status(),
+        ("backup", Some(m)) => backups::cmd_backup(m),
+        ("status", Some(m)) => cmd_status(m.is_present("all")),
+        _ => cmd_status(false),
+    }
+}
+
+/// The state file can't be parsed, offer to restore the most recent backup
+/// instead, if there is one and someone is around to answer.
+fn recover(err: &SqError) -> Result<()> {
+    let refuse = || {
+        Err(SqError::Parse(
+            "State file is unreadable, refusing to continue so it doesn't get overwritten."
+                .to_string(),
+        ))
+    };
+    eprintln!("ERROR: {err}");
+    let SqError::Corrupt { path, backup, .. } = err else {
+        return refuse();
     };
 
-    if let Err(e) = res {
-        let f = Style::new().bold().fg(Color::Red);
-        eprintln!("{}", f.paint(e.to_string()));
+    let Some(backup) = backup else {
+        eprintln!("No backup to restore from, fix the state file by hand.");
+        return refuse();
+    };
+
+    if !std::io::stdin().is_terminal() {
+        eprintln!(
+            "Most recent backup is {}, run sq interactively to restore it, or pick one from `sq backup list` and run `sq backup restore <id> --yes`.",
+            backup.display()
+        );
+        return refuse();
+    }
+
+    let prompt = format!("Restore from most recent backup, {}?", backup.display());
+    if !yes_no_predicate(&prompt)? {
+        return refuse();
     }
+
+    recovery::restore(path, backup)?;
+    println!("Restored state from {}.", backup.display());
+    Ok(())
 }
 
+// Just the list of subcommands and their arguments, splitting it up doesn't
+// make it any easier to read.
+#[allow(clippy::too_many_lines)]
 fn parse_args() -> clap::ArgMatches<'static> {
     App::new("sq")
         .version("0.1")
         .author("Mordecai Malignatus <mordecai@malignat.us>")
         .about("The tiniest spending queue")
-        .subcommand(App::new("status").about("Report the current state"))
+        .arg(
+            Arg::with_name("state")
+                .help("Use this state file instead of the default one. Overrides $SQ_STATE and $SQ_HOME.")
+                .long("state")
+                .value_name("path")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("queue")
+                .help("Act on this queue instead of the selected one, without selecting it.")
+                .short("q")
+                .long("queue")
+                .value_name("name")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("Print output for scripts instead of people, see the README for the fields.")
+                .long("format")
+                .value_name("format")
+                .possible_values(&output::FORMATS)
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("now")
+                .help("Pretend it's this time, as RFC 3339 or RFC 2822. For debugging accrual.")
+                .long("now")
+                .value_name("timestamp")
+                .takes_value(true)
+                .global(true)
+                .hidden(true),
+        )
+        .subcommand(
+            App::new("status").about("Report the current state").arg(
+                Arg::with_name("all")
+                    .help("Report on every queue, not just the selected one")
+                    .short("a")
+                    .long("all"),
+            ),
+        )
         .subcommand(
             App::new("budget")
                 .about("Change budget.")
@@ -148,216 +309,339 @@ fn parse_args() -> clap::ArgMatches<'static> {
                 )
                 .arg(
                     Arg::with_name("force")
-                        .help("Force purchase despite not enough budget being accrued. This will push the balance into the negative.")
+                        .help("Force purchase despite not enough budget being accrued. This will push the balance into the negative, as far as `sq policy --overdraft` allows.")
                         .short("f")
                         .long("force")
                         .takes_value(false)
                         .required(false)
                 )
+                .arg(target_arg())
         )
-        .subcommand(App::new("delete").about("Delete item at head at queue."))
+        .subcommand(App::new("delete").about("Delete item at head at queue.").arg(target_arg()))
         .subcommand(App::new("list").about("Print items remaining to be bought."))
         .subcommand(App::new("past").about("Print items that were already marked as bought."))
-        .subcommand(App::new("bump").about("Move current head of queue back 1-3 spots."))
-        .subcommand(App::new("pause").about("Pause accumulation of the queue."))
-        .subcommand(App::new("unpause").about("Unpause accumulation of the queue."))
+        .subcommand(App::new("forecast").about("Print when each item can be bought, buying them in order."))
+        .subcommand(App::new("ledger").about("Print every change to the balance and why it happened."))
+        .subcommand(App::new("bump").about("Move current head of queue back a few spots.").arg(target_arg()))
         .subcommand(
-            App::new("add")
-                .about("Add an item to the queue")
-                .setting(AppSettings::TrailingVarArg)
+            App::new("move")
+                .about("Move an item to another position in the queue.")
+                .arg(target_arg().required(true))
                 .arg(
-                    Arg::with_name("prepend")
-                        .long("--prepend")
-                        .short("p")
-                        .help("Push new item to the head of the queue instead of the last spot")
-                        .takes_value(false)
-                        .required(false),
+                    Arg::with_name("position")
+                        .help("Where to put it, 1 being the head of the queue")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(items::edit_subcommand())
+        .subcommand(items::show_subcommand())
+        .subcommand(App::new("undo").about("Take back the last change, ie an accidental delete."))
+        .subcommand(App::new("redo").about("Reapply the last change that was undone."))
+        .subcommand(
+            App::new("pause")
+                .about("Pause accumulation of the queue.")
+                .arg(
+                    Arg::with_name("until")
+                        .help("Resume by itself at this date or time, ie 2021-06-18")
+                        .short("u")
+                        .long("until")
+                        .value_name("date")
+                        .takes_value(true),
                 )
-                .arg(Arg::from_usage("<words>... 'Description of thing to buy'")),
+                .arg(
+                    Arg::with_name("all")
+                        .help("Pause every queue, until `sq unpause --all`")
+                        .short("a")
+                        .long("all")
+                        .conflicts_with("until"),
+                ),
         )
+        .subcommand(
+            App::new("unpause")
+                .about("Unpause accumulation of the queue.")
+                .arg(
+                    Arg::with_name("all")
+                        .help("Undo `sq pause --all`. Queues paused on their own stay paused.")
+                        .short("a")
+                        .long("all"),
+                ),
+        )
+        .subcommand(items::add_subcommand())
+        .subcommand(balance::deposit_subcommand())
+        .subcommand(balance::withdraw_subcommand())
+        .subcommand(balance::transfer_subcommand())
+        .subcommand(income::subcommand())
+        .subcommand(policy::subcommand())
+        .subcommand(simulate::subcommand())
         .subcommand(queues::subcommand())
+        .subcommand(backups::subcommand())
         .get_matches()
 }
 
+/// Argument picking the item a command acts on, instead of the head of the
+/// queue.
+fn target_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("item")
+        .help(
+            "ID or position of the item, as shown by `sq list`. Defaults to the head of the queue.",
+        )
+        .takes_value(true)
+}
+
 fn cmd_buy(
+    target: Option<&str>,
     suppress_opening_url: bool,
     new_price: Option<M>,
     peek: bool,
     force: bool,
 ) -> Result<()> {
-    let mut q = currently_selected_queue();
+    let session = session()?;
+    let q = session.selected_queue()?;
 
-    if peek {
-        open_url(
-            &q.future_purchases
-                .front()
-                .expect("Can't peek non-existent item")
-                .purchase_link,
-        )?;
-    } else {
-        match q.future_purchases.front_mut() {
-            Some(item) => {
-                let cost = match new_price {
-                    Some(x) => x,
-                    None => item.amount,
-                };
-                if (cost < q.current_balance) || force {
-                    if !suppress_opening_url {
-                        open_url(&item.purchase_link.clone())?;
-                    }
+    let item = match engine::target_item(&q, target) {
+        Err(SqError::EmptyQueue(_)) if peek => Err(SqError::EmptyQueue(
+            "No item in the queue, can't peek at it!".to_string(),
+        )),
+        Err(SqError::EmptyQueue(_)) => Err(SqError::EmptyQueue(
+            "No item in the queue, can't buy it!".to_string(),
+        )),
+        res => res,
+    }?;
 
-                    if yes_no_predicate(&format!("Did the item cost {}?", cost)) {
-                        purchase_next(cost, &mut q);
-                    } else {
-                        let cost = parse_float_from_stdin("What did it cost?").into();
-                        purchase_next(cost, &mut q);
-                    }
-                } else {
-                    eprintln!("Can't buy item, not enough money accumulated.");
-                }
-            }
-            None => {
-                eprintln!("No item in the queue, can't buy it!");
-            }
-        }
+    if peek {
+        open_url(item.purchase_link.as_deref())?;
+        return Ok(());
     }
 
-    write_current_queue(q)
-}
-
-fn purchase_next(cost: M, queue: &mut Queue) {
-    let now = Local::now().to_rfc2822();
-    let mut item = queue.future_purchases.pop_front().unwrap();
+    let cost = new_price.unwrap_or(item.amount);
+    engine::check_affordable(&q, cost, force, session.clock())?;
 
-    item.time_purchased = Some(now);
-    item.amount = cost;
-    let current_amount_string = format!("{:#.2}", queue.current_balance - cost);
-    let item_amount_string = format!("{:#.2}", cost);
+    if !suppress_opening_url {
+        open_url(item.purchase_link.as_deref())?;
+    }
 
-    println!(
-        "Bought {} for ${}. Remaining: ${}",
-        Style::new().bold().paint(&item.name),
-        Style::new().bold().paint(item_amount_string),
-        Style::new().bold().paint(current_amount_string)
-    );
+    // Ask before taking the lock, so a pending prompt doesn't block every
+    // other sq invocation.
+    let cost = if yes_no_predicate(&format!("Did the item cost {cost}?"))? {
+        cost
+    } else {
+        parse_money_from_stdin("What did it cost?")?
+    };
 
-    queue.current_balance -= cost;
-    queue.past_purchases.push_back(item);
+    let event = session.update_selected_queue("buy", |queue| {
+        engine::buy(queue, &item.id, cost, force, session.clock())
+    })?;
+    report(&event);
+    Ok(())
 }
 
-/// Move current head of queue back 1-3 spots. This is essentially a "not right
-/// now" button for reordering the queue.
-fn cmd_bump() -> Result<()> {
-    let mut queue = currently_selected_queue();
-    let bold = Style::new().bold();
+fn cmd_bump(target: Option<&str>) -> Result<()> {
+    let event = session()?.update_selected_queue("bump", |queue| {
+        engine::bump(queue, target, &mut rand::thread_rng())
+    })?;
+    report(&event);
 
-    match queue.future_purchases.len() {
-        0 => eprintln!("No items in the queue, can't bump anything."),
-        1 => eprintln!("One item in the queue, can't bump anything."),
-        2 => {
-            let head = queue.future_purchases.pop_front().unwrap();
-            let name = head.name.clone();
-            // Exhange the first two items. If we do this with the code below,
-            // we ask `rand` to sample a range of 1..=1, causing a panic.
-            queue.future_purchases.insert(1, head);
-            println!(
-                "Moved {} from head of queue to position {}. Next item is now {}.",
-                bold.paint(name),
-                bold.paint("1"),
-                bold.paint(queue.future_purchases.front().unwrap().name.clone())
-            );
-            write_current_queue(queue)?;
-            cmd_status()?;
-        }
-        _ => {
-            let head = queue.future_purchases.pop_front().expect(
-                "We already checked for queue length, thus must succeed in any sane universe",
-            );
-            let head_name = head.name.clone();
-            // Subtract 1 to go from length of list to valid, zero-based indices.
-            let upper_bound = queue.future_purchases.len() - 1;
-            // The x..=y syntax is an inclusive range, the x..y syntax by
-            // default is *exclusive*, meaning that a range like 1..2 is empty,
-            // and causes `rand` to panic.
-            let new_position = rand::thread_rng().gen_range(1..=upper_bound);
-            queue.future_purchases.insert(new_position, head);
-            println!(
-                "Moved {} from head of queue to position {}. Next item is now {}.",
-                bold.paint(&head_name),
-                bold.paint((new_position + 1).to_string()),
-                bold.paint(queue.future_purchases.front().unwrap().name.clone())
-            );
-            write_current_queue(queue)?;
-            cmd_status()?;
-        }
+    if output::is_text() {
+        cmd_status(false)?;
     }
+    Ok(())
+}
+
+fn cmd_move(target: &str, position: usize) -> Result<()> {
+    let event = session()?
+        .update_selected_queue("move", |queue| engine::move_item(queue, target, position))?;
+    report(&event);
+    Ok(())
+}
 
+fn cmd_pause(until: Option<DateTime<Local>>) -> Result<()> {
+    let session = session()?;
+    let event = session.update_selected_queue("pause", |queue| {
+        engine::pause(queue, until, session.clock())
+    })?;
+    report(&event);
     Ok(())
 }
 
-fn cmd_pause() -> Result<()> {
-    let mut queue = currently_selected_queue();
-    queue.paused = true;
-    println!("Paused accumulation. Run `sq unpause` to resume.");
-    write_current_queue(queue)
+fn cmd_pause_all() -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("pause --all", |state| {
+        engine::pause_all(state, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
+
+fn cmd_unpause_all() -> Result<()> {
+    let session = session()?;
+    let event = session.update_state("unpause --all", |state| {
+        engine::unpause_all(state, session.clock())
+    })?;
+    report(&event);
+    Ok(())
 }
 
 fn cmd_unpause() -> Result<()> {
-    let mut queue = currently_selected_queue();
-    queue.paused = false;
-    println!("Unpaused accumulation, welcome back.");
-    write_current_queue(queue)
-}
-
-fn cmd_delete() -> Result<()> {
-    let mut queue = currently_selected_queue();
-    if let Some(item) = queue.future_purchases.pop_front() {
-        println!("Deleted item at head of queue: {}", item.name);
-        write_current_queue(queue)?;
-        cmd_status()
-    } else {
-        eprintln!("No item in queue, can't remove any.");
-        Ok(())
+    let session = session()?;
+    let event = session
+        .update_selected_queue("unpause", |queue| engine::unpause(queue, session.clock()))?;
+    report(&event);
+    Ok(())
+}
+
+fn cmd_delete(target: Option<&str>) -> Result<()> {
+    let event =
+        session()?.update_selected_queue("delete", |queue| engine::delete(queue, target))?;
+    report(&event);
+    if output::is_text() {
+        cmd_status(false)?;
     }
+    Ok(())
 }
 
-// We return a result to make main have a uniform return type for subcommands,
-// even if it is not needed here.
-#[allow(clippy::unnecessary_wraps)]
 /// Print the list as it is right now.
 fn cmd_list() -> Result<()> {
-    let queue = currently_selected_queue();
+    let queue = session()?.selected_queue()?;
+    if !output::is_text() {
+        let records: Vec<Value> = queue
+            .future_purchases
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let mut record = output::item(item);
+                record["position"] = json!(i + 1);
+                record
+            })
+            .collect();
+        let mut columns = vec!["position"];
+        columns.extend(output::ITEM_COLUMNS);
+        output::print_records(&records, &columns);
+        return Ok(());
+    }
+
     let mut table = Table::new();
-    table.set_titles(row!("Name", "Cost"));
+    table.set_titles(row!("#", "ID", "Name", "Cost", "Tags"));
     table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
-    queue.future_purchases.iter().for_each(|item| {
-        let cost = format!("${:#.2}", item.amount);
-        if item.purchase_link.is_some() {
-            table.add_row(row!(bi->item.name, cost));
-        } else {
-            table.add_row(row!(b->item.name, cost));
+    queue
+        .future_purchases
+        .iter()
+        .enumerate()
+        .for_each(|(i, item)| {
+            let cost = format!("${:#.2}", item.amount);
+            let tags = item.tags.join(", ");
+            if item.purchase_link.is_some() {
+                table.add_row(row!(r->i + 1, item.id, bi->item.name, cost, tags));
+            } else {
+                table.add_row(row!(r->i + 1, item.id, b->item.name, cost, tags));
+            }
+        });
+
+    table.printstd();
+    println!();
+    Ok(())
+}
+
+/// Print when each item becomes affordable, if everything is bought in order
+/// as soon as possible.
+fn cmd_forecast() -> Result<()> {
+    let session = session()?;
+    let queue = session.selected_queue()?;
+    let now = session.clock().now();
+    let forecasts = engine::forecast(&queue, session.clock())?;
+
+    if !output::is_text() {
+        let records: Vec<Value> = forecasts
+            .iter()
+            .enumerate()
+            .map(|(i, forecast)| {
+                let mut record = output::item(&forecast.item);
+                record["position"] = json!(i + 1);
+                record["affordable_at"] = json!(forecast.affordable_at.map(|at| at.to_rfc2822()));
+                record
+            })
+            .collect();
+        let mut columns = vec!["position"];
+        columns.extend(output::ITEM_COLUMNS);
+        columns.push("affordable_at");
+        output::print_records(&records, &columns);
+        return Ok(());
+    }
+
+    let limit = engine::balance_limit(&queue);
+    let never = if paused_for_good(&queue, session.clock())? {
+        "not while paused"
+    } else {
+        "not at this income"
+    };
+    // The first item that can't ever be afforded holds up everything behind it.
+    let mut blocker = None;
+    let mut table = Table::new();
+    table.set_titles(row!("#", "ID", "Name", "Cost", "Affordable"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    for (i, forecast) in forecasts.iter().enumerate() {
+        let cost = format!("${:#.2}", forecast.item.amount);
+        let too_expensive = limit.is_some_and(|limit| forecast.item.amount > limit);
+        let when = match (forecast.affordable_at, blocker) {
+            (Some(at), _) if at <= now => "now".to_string(),
+            (Some(at), _) => format!(
+                "{} (in {})",
+                at.format("%Y-%m-%d %H:%M"),
+                format_duration(at - now)
+            ),
+            (None, _) if too_expensive => "never, costs more than the queue keeps".to_string(),
+            (None, Some(blocker)) => format!("after {blocker}"),
+            (None, None) => never.to_string(),
         };
-    });
+        if too_expensive && blocker.is_none() {
+            blocker = Some(&forecast.item.name);
+        }
+        table.add_row(row!(r->i + 1, forecast.item.id, b->forecast.item.name, r->cost, when));
+    }
 
     table.printstd();
     println!();
     Ok(())
 }
 
-// We return a result to make main have a uniform return type for subcommands,
-// even if it is not needed here.
-#[allow(clippy::unnecessary_wraps)]
+/// A rough, human sized duration, ie `4d 6h`.
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => "less than a minute".to_string(),
+        (0, 0, m) => format!("{m}m"),
+        (0, h, m) => format!("{h}h {m}m"),
+        (d, h, _) => format!("{d}d {h}h"),
+    }
+}
+
 /// Print list of past purchases, the things already bought.
 fn cmd_past() -> Result<()> {
-    let queue = currently_selected_queue();
+    let queue = session()?.selected_queue()?;
+    if !output::is_text() {
+        let records: Vec<Value> = queue
+            .past_purchases
+            .iter()
+            .map(|item| {
+                let mut record = output::item(item);
+                record["purchased"] = json!(item.time_purchased);
+                record
+            })
+            .collect();
+        let mut columns = output::ITEM_COLUMNS.to_vec();
+        columns.push("purchased");
+        output::print_records(&records, &columns);
+        return Ok(());
+    }
 
     let mut table = Table::new();
-    table.set_titles(row!("Name", "Cost", "Purchased"));
+    table.set_titles(row!("ID", "Name", "Cost", "Purchased"));
     table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
     queue.past_purchases.iter().for_each(|item| {
         let cost = format!("${:#.2}", item.amount);
         let ts = item.time_purchased.clone().unwrap_or_default();
-        table.add_row(row!(b->item.name, cost, ts));
+        table.add_row(row!(item.id, b->item.name, cost, ts));
     });
 
     table.printstd();
@@ -365,37 +649,130 @@ fn cmd_past() -> Result<()> {
     Ok(())
 }
 
-fn cmd_add(thing_to_add: String, prepend: bool) -> Result<()> {
-    let parsed = parse_float_from_stdin("What does this cost?: ");
-    let purchase_url = read_stdin_line("Do you have a purchase URL? (Leave empty for no)");
+/// Print the ledger, every change to the balance with a running total.
+fn cmd_ledger() -> Result<()> {
+    let session = session()?;
+    let queue = session.selected_queue()?;
+    if !output::is_text() {
+        let mut running = M::from(0);
+        let records: Vec<Value> = queue
+            .ledger
+            .iter()
+            .map(|entry| {
+                running += entry.amount;
+                json!({
+                    "time": entry.time,
+                    "kind": entry.kind,
+                    "amount": output::money(entry.amount),
+                    "balance": output::money(running),
+                    "reason": entry.reason,
+                })
+            })
+            .collect();
+        output::print_records(&records, &["time", "kind", "amount", "balance", "reason"]);
+        return Ok(());
+    }
 
-    let purchase_link = match purchase_url.as_ref() {
-        "" => None,
-        _ => Some(purchase_url),
-    };
+    let mut table = Table::new();
+    table.set_titles(row!("Time", "Kind", "Amount", "Balance", "Reason"));
+    table.set_format(*FORMAT_NO_BORDER_LINE_SEPARATOR);
+    let mut running = M::from(0);
+    queue.ledger.iter().for_each(|entry| {
+        running += entry.amount;
+        let amount = format!("${:#.2}", entry.amount);
+        let balance = format!("${running:#.2}");
+        table.add_row(row!(entry.time, entry.kind, r->amount, r->balance, entry.reason));
+    });
 
-    let amount = format!("{:#.2}", M::from(parsed));
-    println!("Adding \"{}\" for ${} to the list.", &thing_to_add, amount);
-    let mut queue = currently_selected_queue();
-    let item = Item {
-        name: thing_to_add,
-        amount: M::from(parsed),
-        purchase_link,
-        time_purchased: None,
-    };
+    table.printstd();
+    println!();
 
-    if prepend {
-        queue.future_purchases.push_front(item);
-    } else {
-        queue.future_purchases.push_back(item);
+    let (_, balance) = calculate_current_amount(&queue, session.clock())?;
+    let pending = balance - queue.current_balance;
+    if !pending.is_zero() {
+        println!(
+            "Accrued since {}, not yet settled: ${pending:#.2}",
+            queue.last_calculation
+        );
+    }
+    if !(queue.ledger_balance() - queue.current_balance).is_zero() {
+        eprintln!(
+            "{}",
+            Style::new().bold().paint(format!(
+                "Ledger adds up to ${:#.2}, but the stored balance is ${:#.2}. Was the state edited by hand?",
+                queue.ledger_balance(),
+                queue.current_balance
+            ))
+        );
     }
+    Ok(())
+}
 
-    write_current_queue(queue)
+/// Put the state back to how it was before the last change.
+fn cmd_undo() -> Result<()> {
+    step_journal(Journal::undo, ("Undid", "undone"), "Nothing to undo.")
 }
 
-fn cmd_status() -> Result<()> {
-    let state = read_state_file();
-    let bold = Style::new().bold();
+/// Reapply the last change that was undone.
+fn cmd_redo() -> Result<()> {
+    step_journal(Journal::redo, ("Redid", "redone"), "Nothing to redo.")
+}
+
+fn step_journal(
+    step: fn(&Journal, &dyn Storage, &State, Notify) -> Result<Option<Snapshot>>,
+    (done, event): (&str, &str),
+    nothing: &str,
+) -> Result<()> {
+    let session = session()?;
+    let (store, _lock, current) = session.begin_change()?;
+    let journal = Journal::open(&*store);
+
+    match step(&journal, &*store, &current, session.notify())? {
+        Some(snapshot) if !output::is_text() => {
+            let record = json!({
+                "event": event,
+                "action": snapshot.action,
+                "time": snapshot.time,
+            });
+            output::print_record(&record, &["event", "action", "time"]);
+        }
+        Some(snapshot) => {
+            println!(
+                "{} `sq {}` from {}.",
+                done,
+                Style::new().bold().paint(&snapshot.action),
+                snapshot.time
+            );
+        }
+        None => eprintln!("{nothing}"),
+    }
+
+    Ok(())
+}
+
+/// Print the status of the queue the session acts on, or with `all`, of
+/// every queue.
+fn cmd_status(all: bool) -> Result<()> {
+    let session = session()?;
+    let state = session.load()?;
+    let queues: Vec<&Queue> = if all {
+        state.queues.iter().collect()
+    } else {
+        vec![state.queue(session.queue_name(&state))?]
+    };
+
+    if !output::is_text() {
+        let mut records = Vec::new();
+        for queue in &queues {
+            records.push(status_record(&state, queue, session.clock())?);
+        }
+        if all {
+            output::print_records(&records, &STATUS_COLUMNS);
+        } else {
+            output::print_record(&records[0], &STATUS_COLUMNS);
+        }
+        return Ok(());
+    }
 
     if state.globally_paused {
         println!(
@@ -403,154 +780,475 @@ fn cmd_status() -> Result<()> {
             Style::new()
                 .italic()
                 .bold()
-                .paint("SQ is currently paused. To unpause, run `sq unpause`.")
+                .paint("SQ is currently paused. To unpause, run `sq unpause --all`.")
         );
-        Ok(())
-    } else {
-        let mut queue = currently_selected_queue();
-        update_accumulation(&mut queue);
+    }
+    if !all {
+        print_status(queues[0], session.clock())?;
+        return print_other_pauses(&state, queues[0], session.clock());
+    }
 
-        let available_amount = format!("{:#.2}", queue.current_balance);
-        println!(
-            "Currently available free budget: ${}",
-            Style::new().bold().paint(&available_amount)
-        );
+    let mut total = M::from(0);
+    for queue in queues {
+        let mut title = queue.name.clone();
+        if queue.name == state.currently_selected {
+            title.push_str(" (selected)");
+        }
+        if queue.archived {
+            title.push_str(" (archived)");
+        }
+        println!("{}", Style::new().bold().underline().paint(title));
+        if let Some(share) = income::describe_share(&state, &queue.name) {
+            println!("Gets {share}.");
+        }
+        print_status(queue, session.clock())?;
+        total += calculate_current_amount(queue, session.clock())?.1;
+    }
+    println!(
+        "Available across all queues: ${}",
+        Style::new().bold().paint(format!("{total:#.2}"))
+    );
+    println!();
+    Ok(())
+}
 
-        match queue.future_purchases.front() {
-            Some(item) => {
-                let amount = format!("{:#.2}", item.amount);
-                let name = match &item.purchase_link {
-                    Some(_) => Style::new().bold().italic().paint(item.name.clone()),
-                    None => bold.paint(item.name.clone()),
-                };
+/// Columns of `status_record` for CSV and TSV.
+const STATUS_COLUMNS: [&str; 24] = [
+    "queue",
+    "balance",
+    "income.amount",
+    "income.interval_in_days",
+    "paused",
+    "paused_until",
+    "globally_paused",
+    "items",
+    "next_item.id",
+    "next_item.name",
+    "next_item.price",
+    "affordable",
+    "affordable_at",
+    "share.rule",
+    "share.value",
+    "policy.cap.amount",
+    "policy.cap.spill_to",
+    "policy.expiry.after_days",
+    "policy.expiry.sweep_to",
+    "policy.overdraft.kind",
+    "policy.overdraft.limit",
+    "debt.amount",
+    "debt.repaid_at",
+    "debt.delayed",
+];
 
-                println!(
-                    "The next item in the queue is {} for ${}",
-                    name,
-                    bold.paint(&amount)
-                );
-                if queue.current_balance >= item.amount {
-                    println!("{}", bold.paint("*** NEXT ITEM PURCHASEABLE ***"));
-                }
-            }
-            None => println!("There's no next item in the queue, add one!"),
-        };
+/// The status of `queue`, for scripts.
+fn status_record(state: &State, queue: &Queue, clock: &dyn Clock) -> Result<Value> {
+    let (_, balance) = calculate_current_amount(queue, clock)?;
+    let pauses = engine::current_pauses(queue, clock.now())?;
+    let next = queue.future_purchases.front();
 
-        println!();
-        write_current_queue(queue)
-    }
+    Ok(json!({
+        "queue": queue.name,
+        "balance": output::money(balance),
+        "income": {
+            "amount": queue.income.amount,
+            "interval_in_days": queue.income.interval_in_days,
+        },
+        "paused": !pauses.is_empty(),
+        // Only set if every pause ends, and then to the last of them.
+        "paused_until": if pauses.iter().any(|p| p.until.is_none()) {
+            None
+        } else {
+            pauses.iter().filter_map(|p| p.until.clone()).max_by_key(|until| {
+                DateTime::parse_from_rfc2822(until).ok()
+            })
+        },
+        "globally_paused": state.globally_paused,
+        "items": queue.future_purchases.len(),
+        "next_item": next.map(output::item),
+        "affordable": next.is_some_and(|item| balance >= item.amount),
+        "affordable_at": next_affordable(queue, clock)?.map(|at| at.to_rfc2822()),
+        "share": income::share_record(state, &queue.name),
+        "policy": queue.policy,
+        "debt": engine::debt(queue, clock)?.map(|debt| json!({
+            "amount": output::money(debt.amount),
+            "repaid_at": debt.repaid_at.map(|at| at.to_rfc2822()),
+            "delayed": debt.delayed.iter().map(|item| &item.id).collect::<Vec<_>>(),
+        })),
+    }))
+}
+
+/// Whether the queue is paused until further notice, so income won't pay
+/// for anything.
+fn paused_for_good(queue: &Queue, clock: &dyn Clock) -> Result<bool> {
+    let pauses = engine::current_pauses(queue, clock.now())?;
+    Ok(pauses.iter().any(|pause| pause.until.is_none()))
 }
 
-fn update_accumulation(queue: &mut Queue) {
-    let (new_timestamp, new_amount) = calculate_current_amount(queue);
-    queue.last_calculation = new_timestamp.to_rfc2822();
-    queue.current_balance = if queue.paused {
-        queue.current_balance
+/// Why income never gets there, ie for a debt or the next item.
+fn never_because(queue: &Queue, clock: &dyn Clock) -> Result<&'static str> {
+    Ok(if paused_for_good(queue, clock)? {
+        "while the queue is paused"
     } else {
-        new_amount
-    };
+        "at the current income"
+    })
 }
 
-fn calculate_current_amount(queue: &Queue) -> (DateTime<Local>, M) {
-    let now = Local::now();
-    let then = DateTime::parse_from_rfc2822(&queue.last_calculation)
-        .expect("Can't parse date from last calculation, check the statefile");
-    let time_between = now.signed_duration_since(then).num_seconds();
-    let time_between = M::from(time_between);
-    let income = M::from(queue.income.amount);
+/// When the head of the queue becomes affordable, see `engine::forecast`.
+fn next_affordable(queue: &Queue, clock: &dyn Clock) -> Result<Option<DateTime<Local>>> {
+    let forecasts = engine::forecast(queue, clock)?;
+    Ok(forecasts
+        .first()
+        .and_then(|forecast| forecast.affordable_at))
+}
 
-    let interval = M::from(queue.income.interval_in_days as u64);
-    let current_balance = queue.current_balance;
+/// Name the queues besides the `shown` one that are paused on their own, the
+/// ones paused along with everything else are covered by the banner.
+fn print_other_pauses(state: &State, shown: &Queue, clock: &dyn Clock) -> Result<()> {
+    let mut paused = Vec::new();
+    for queue in state.queues.iter().filter(|q| q.name != shown.name) {
+        let current = engine::current_pauses(queue, clock.now())?;
+        if let Some(pause) = current.iter().find(|p| !p.global) {
+            paused.push(match &pause.until {
+                Some(until) => format!("{} (until {until})", queue.name),
+                None => queue.name.clone(),
+            });
+        }
+    }
 
-    let seconds_in_interval = M::from(24_u64 * 60 * 60) * interval;
-    let money_per_second = income / seconds_in_interval;
-    let since_last_calc = time_between * money_per_second;
+    if !paused.is_empty() {
+        println!("Other queues paused on their own: {}", paused.join(", "));
+        println!();
+    }
+    Ok(())
+}
 
-    let subtotal = current_balance + since_last_calc;
+fn print_status(queue: &Queue, clock: &dyn Clock) -> Result<()> {
+    let bold = Style::new().bold();
+    let (_, balance) = calculate_current_amount(queue, clock)?;
 
-    (now, subtotal)
-}
+    let available_amount = format!("{balance:#.2}");
+    println!(
+        "Currently available free budget: ${}",
+        Style::new().bold().paint(&available_amount)
+    );
+    for pause in engine::current_pauses(queue, clock.now())? {
+        let message = match (&pause.until, pause.global) {
+            (_, true) => "Accumulation is paused along with all other queues.".to_string(),
+            (Some(until), false) => format!("Accumulation is paused until {until}."),
+            (None, false) => "Accumulation is paused until `sq unpause`.".to_string(),
+        };
+        println!("{}", Style::new().italic().paint(message));
+    }
+    print_debt(queue, clock)?;
+    policy::print_warnings(queue, balance, clock)?;
 
-fn cmd_budget(amount: f64, interval: u64) -> Result<()> {
-    let mut queue = currently_selected_queue();
+    match queue.future_purchases.front() {
+        Some(item) => {
+            let amount = format!("{:#.2}", item.amount);
+            let name = match &item.purchase_link {
+                Some(_) => Style::new().bold().italic().paint(item.name.clone()),
+                None => bold.paint(item.name.clone()),
+            };
 
-    println!("Updated income to ${:.2} per {} days.", amount, interval);
-    queue.income = Income {
-        amount,
-        interval_in_days: interval,
-    };
-    write_current_queue(queue)
+            println!(
+                "The next item in the queue is {} for ${}",
+                name,
+                bold.paint(&amount)
+            );
+            if balance >= item.amount {
+                println!("{}", bold.paint("*** NEXT ITEM PURCHASEABLE ***"));
+            } else {
+                match next_affordable(queue, clock)? {
+                    Some(at) => println!(
+                        "Next item affordable in {}",
+                        bold.paint(format_duration(at - clock.now()))
+                    ),
+                    None if engine::balance_limit(queue)
+                        .is_some_and(|limit| item.amount > limit) =>
+                    {
+                        println!("Next item costs more than the queue keeps, see `sq policy`.");
+                    }
+                    None => {
+                        println!(
+                            "Next item won't become affordable {}.",
+                            never_because(queue, clock)?
+                        );
+                    }
+                }
+            }
+        }
+        None => println!("There's no next item in the queue, add one!"),
+    }
+
+    println!();
+    Ok(())
 }
 
-#[must_use]
-pub fn config_file_path() -> PathBuf {
-    let home = env::var("HOME").expect("$HOME is not set, aborting.");
-    let mut home = PathBuf::from(home);
-    home.push(".config");
-    home.push("sq");
-    home.push("state.json");
+/// If the balance is below zero, say when that's paid off and what it holds
+/// up until then.
+fn print_debt(queue: &Queue, clock: &dyn Clock) -> Result<()> {
+    let Some(debt) = engine::debt(queue, clock)? else {
+        return Ok(());
+    };
+    let bold = Style::new().bold();
+    let amount = bold.paint(format!("{:#.2}", debt.amount));
+    match debt.repaid_at {
+        Some(at) => println!(
+            "In debt by ${amount}, paid off on {} (in {}) at the current income.",
+            at.format("%Y-%m-%d"),
+            format_duration(at - clock.now())
+        ),
+        None => println!(
+            "In debt by ${amount}, which isn't paid off {}.",
+            never_because(queue, clock)?
+        ),
+    }
 
-    home
+    let names: Vec<&str> = debt.delayed.iter().map(|item| item.name.as_str()).collect();
+    let held_up = match names.len() {
+        0 => return Ok(()),
+        1 => names[0].to_string(),
+        2 | 3 => format!(
+            "{} and {}",
+            names[..names.len() - 1].join(", "),
+            names[names.len() - 1]
+        ),
+        n => format!("{} and {} more", names[..2].join(", "), n - 2),
+    };
+    println!("Until then, it holds up {held_up}.");
+    Ok(())
 }
 
-fn read_state_file() -> State {
-    let statepath = config_file_path();
-    if !statepath.exists() {
-        let mut t = config_file_path();
-        let _ = t.pop();
-        std::fs::create_dir_all(t).expect("can't create config dir");
+fn cmd_budget(income: Income) -> Result<()> {
+    let session = session()?;
+    let state = session.load()?;
+    let name = session.queue_name(&state);
+    if state.share(name).is_some() {
+        return Err(SqError::Validation(format!(
+            "Queue {name} gets its income from the shared income, change that with `sq income`."
+        )));
     }
+    let event = session.update_selected_queue("budget", |queue| {
+        engine::set_income(queue, income, session.clock())
+    })?;
+    report(&event);
+    Ok(())
+}
 
-    let content = match fs::read_to_string(statepath) {
-        Ok(s) => s,
-        Err(err) => {
-            eprintln!(
-                "ERROR: Can't read config file contents: {}",
-                err.to_string()
+/// Tell the user what an operation did.
+#[allow(clippy::too_many_lines)]
+fn report(event: &Event) {
+    if !output::is_text() {
+        output::print_event(event);
+        return;
+    }
+    let bold = Style::new().bold();
+    match event {
+        Event::Bought { item, remaining } => println!(
+            "Bought {} for ${}. Remaining: ${}",
+            bold.paint(&item.name),
+            bold.paint(format!("{:#.2}", item.amount)),
+            bold.paint(format!("{remaining:#.2}"))
+        ),
+        Event::Bumped {
+            item,
+            from,
+            position,
+            next,
+        } => println!(
+            "Moved {} from {} to position {}. Next item is now {}.",
+            bold.paint(item),
+            if *from == 1 {
+                "head of queue".to_string()
+            } else {
+                format!("position {}", bold.paint(from.to_string()))
+            },
+            bold.paint(position.to_string()),
+            bold.paint(next)
+        ),
+        Event::Added { item, .. } => println!(
+            "Added \"{}\" for ${:#.2} to the list.",
+            item.name, item.amount
+        ),
+        Event::AddedAll { items, .. } => {
+            let total = items
+                .iter()
+                .fold(M::from(0), |total, item| total + item.amount);
+            println!(
+                "Added {} items for ${total:#.2} in total to the list.",
+                items.len()
             );
-            std::process::exit(1)
         }
-    };
-
-    match serde_json::from_str(&content) {
-        Ok(s) => s,
-        Err(_) => {
-            if let Ok(s) = legacy::migrate_statefile() {
-                s
-            } else {
-                eprintln!("Can neither read nor migrate statefile, continuing with default");
-                eprintln!("You're going to want to adjust the income, currently $1 per day.");
-                State::default()
+        Event::Deleted(item) => {
+            println!("Deleted item: {}", item.name);
+            println!("Run `sq undo` to bring it back.");
+        }
+        Event::Paused { until: None } => {
+            println!("Paused accumulation. Run `sq unpause` to resume.");
+        }
+        Event::Paused { until: Some(until) } => println!(
+            "Paused accumulation until {}. Run `sq unpause` to resume earlier.",
+            bold.paint(until.to_rfc2822())
+        ),
+        Event::Unpaused => println!("Unpaused accumulation, welcome back."),
+        Event::PausedAll => {
+            println!("Paused accumulation of every queue. Run `sq unpause --all` to resume.");
+        }
+        Event::UnpausedAll => println!("Unpaused accumulation of every queue, welcome back."),
+        Event::Moved { item, position } => println!(
+            "Moved {} to position {}.",
+            bold.paint(item),
+            bold.paint(position.to_string())
+        ),
+        Event::Edited { before, after } => report_edit(before, after),
+        Event::QueueCreated(name) => println!(
+            "Created queue {}. Run `sq queue select {name}` to use it.",
+            bold.paint(name)
+        ),
+        Event::QueueSelected(name) => println!("Selected queue {}.", bold.paint(name)),
+        Event::QueueRenamed { from, to } => {
+            println!("Renamed queue {from} to {}.", bold.paint(to));
+        }
+        Event::QueueDeleted {
+            name,
+            balance,
+            transferred_to,
+        } => {
+            println!("Deleted queue {}.", bold.paint(name));
+            if let Some(target) = transferred_to {
+                println!(
+                    "Moved its balance of ${balance:#.2} to {}.",
+                    bold.paint(target)
+                );
+            }
+            println!("Run `sq undo` to bring it back.");
+        }
+        Event::QueueArchived(name) => println!(
+            "Archived queue {}. Run `sq queue unarchive {name}` to bring it back.",
+            bold.paint(name)
+        ),
+        Event::QueueUnarchived(name) => println!("Unarchived queue {}.", bold.paint(name)),
+        Event::IncomeChanged { new, .. } => println!(
+            "Updated income to ${:.2} per {} days.",
+            new.amount, new.interval_in_days
+        ),
+        Event::IncomeShared(Some(shared)) => println!(
+            "Sharing ${:.2} per {} days between {}, by {}.",
+            shared.income.amount,
+            shared.income.interval_in_days,
+            shared
+                .shares
+                .iter()
+                .map(|share| share.queue.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            shared.rule
+        ),
+        Event::IncomeShared(None) => {
+            println!("Stopped sharing income, every queue keeps the income it has now.");
+        }
+        Event::Deposited { amount, balance } => println!(
+            "Deposited ${}. Balance: ${}",
+            bold.paint(format!("{amount:#.2}")),
+            bold.paint(format!("{balance:#.2}"))
+        ),
+        Event::Withdrew { amount, balance } => println!(
+            "Withdrew ${}. Remaining: ${}",
+            bold.paint(format!("{amount:#.2}")),
+            bold.paint(format!("{balance:#.2}"))
+        ),
+        Event::Transferred { from, to, amount } => println!(
+            "Moved ${} from {} to {}.",
+            bold.paint(format!("{amount:#.2}")),
+            bold.paint(from),
+            bold.paint(to)
+        ),
+        Event::PolicyChanged { queue, policy } => {
+            println!("Changed the policy of queue {}.", bold.paint(queue));
+            for line in policy::describe(policy) {
+                println!("{line}");
             }
         }
     }
 }
 
-fn write_file(state: &State) -> Result<()> {
-    fs::write(
-        config_file_path(),
-        serde_json::to_string_pretty(state).unwrap(),
-    )
+/// Name every field `edit` changed, with its old and new value.
+fn report_edit(before: &Item, after: &Item) {
+    let none = || "(none)".to_string();
+    let fields = [
+        ("Name", before.name.clone(), after.name.clone()),
+        (
+            "Price",
+            format!("${:#.2}", before.amount),
+            format!("${:#.2}", after.amount),
+        ),
+        (
+            "URL",
+            before.purchase_link.clone().unwrap_or_else(none),
+            after.purchase_link.clone().unwrap_or_else(none),
+        ),
+        (
+            "Notes",
+            before.notes.clone().unwrap_or_else(none),
+            after.notes.clone().unwrap_or_else(none),
+        ),
+    ];
+
+    let bold = Style::new().bold();
+    let mut changed = fields.iter().filter(|(_, old, new)| old != new).peekable();
+    if changed.peek().is_none() {
+        println!("Nothing changed on {}.", bold.paint(&after.name));
+        return;
+    }
+    println!("Updated {}:", bold.paint(&after.name));
+    for (field, old, new) in changed {
+        println!("  {field}: {old} -> {}", bold.paint(new));
+    }
 }
 
-fn write_current_queue(queue: Queue) -> Result<()> {
-    let mut state = read_state_file();
-    let mut nq: Vec<Queue> = state
-        .queues
-        .into_iter()
-        .filter(|q| q.name != queue.name)
-        .collect();
-    nq.push(queue);
-    state.queues = nq;
+/// State location given with `--state`, which takes precedence over the
+/// environment, see `config::locate`.
+static STATE_FLAG: OnceLock<PathBuf> = OnceLock::new();
+
+/// Queue given with `--queue`, used instead of the selected one.
+static QUEUE_FLAG: OnceLock<String> = OnceLock::new();
+
+/// Time given with `--now`, used instead of the system time.
+static NOW_FLAG: OnceLock<DateTime<Local>> = OnceLock::new();
 
-    write_file(&state)
+/// Tell about things the library noticed, on stderr so output for scripts
+/// stays clean.
+fn print_notice(notice: &Notice) {
+    match notice {
+        Notice::Fresh { path } => {
+            eprintln!("No state found at {}, starting fresh.", path.display());
+            eprintln!("You're going to want to adjust the income, currently $1 per day.");
+        }
+        Notice::Migrated { from, to, .. } => {
+            eprintln!("Migrated state file from schema version {from} to {to}, continuing...");
+        }
+        Notice::Imported { from, into } => {
+            eprintln!("Imported {} into {}.", from.display(), into.display());
+        }
+        Notice::JournalDropped { path, reason } => eprintln!(
+            "{}",
+            Style::new().bold().paint(format!(
+                "Can't use undo history {}, starting over: {reason}",
+                path.display()
+            ))
+        ),
+    }
 }
 
-fn currently_selected_queue() -> Queue {
-    let state = read_state_file();
-    let current_name = state.currently_selected.clone();
-    state.queues
-        .into_iter()
-        .find(|q| q.name == current_name)
-        .expect("Currently selected queue does not match any of the actual queues, present, you will need to fix this manually.")
+/// Where the state lives, honouring `--state` and `--now`.
+fn session() -> Result<Session> {
+    let mut session =
+        Session::open(STATE_FLAG.get().map(PathBuf::as_path))?.with_notify(print_notice);
+    if let Some(now) = NOW_FLAG.get() {
+        session = session.with_clock(FakeClock::new(*now));
+    }
+    if let Some(queue) = QUEUE_FLAG.get() {
+        session = session.with_queue(queue.as_str());
+    }
+    Ok(session)
 }
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde_json::json;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::error::SqError;
+use crate::legacy;
+use crate::types::new_item_id;
+use crate::types::State;
+
+/// A single migration step, bringing the state file from one schema version to
+/// the next one.
+type Migration = fn(Value) -> Result<Value>;
+
+/// Ordered registry of migrations. The migration at index `n` upgrades a state
+/// file of schema version `n` to version `n + 1`, so new migrations are only
+/// ever appended to the end.
+const MIGRATIONS: &[Migration] = &[legacy::v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+/// Schema version written by this build of sq.
+#[allow(clippy::cast_possible_truncation)]
+pub const CURRENT_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Figure out which schema a state file is in. Files from before the schema was
+/// versioned are recognised by their shape.
+fn detect_version(value: &Value) -> Result<u32> {
+    match value.get("schema_version") {
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| SqError::Parse(format!("Invalid schema_version in state file: {v}"))),
+        None if value.get("queues").is_some() => Ok(1),
+        None => Ok(0),
+    }
+}
+
+/// Parse the contents of a state file, bringing it forward to the current
+/// schema step by step. Once every step worked, a copy of the file as it was
+/// before each of them is written next to `statefile`, ie
+/// `state.json.v1.bak`. Nothing is written if any step fails, so recovery
+/// never mistakes a half-migrated copy for a backup.
+///
+/// Returns the parsed state and, if any migration ran, the version it was
+/// migrated from, in which case the caller should persist the upgraded state.
+pub fn upgrade(content: &str, statefile: &Path) -> Result<(State, Option<u32>)> {
+    let mut value: Value = serde_json::from_str(content)?;
+    let found = detect_version(&value)?;
+
+    if found > CURRENT_SCHEMA_VERSION {
+        return Err(SqError::Storage(format!(
+            "State file has schema version {found}, but this sq only understands up to version {CURRENT_SCHEMA_VERSION}. Refusing to load it, please upgrade sq."
+        )));
+    }
+
+    if found == CURRENT_SCHEMA_VERSION {
+        // Parse straight from the text, so errors carry line and column.
+        return Ok((serde_json::from_str(content)?, None));
+    }
+
+    let mut before = Vec::new();
+    for version in found..CURRENT_SCHEMA_VERSION {
+        before.push((version, serde_json::to_string_pretty(&value)?));
+        value = MIGRATIONS[version as usize](value)?;
+        value["schema_version"] = Value::from(version + 1);
+    }
+    let state = serde_json::from_value(value)?;
+
+    for (version, content) in before {
+        fs::write(backup_path(statefile, version), content)?;
+    }
+    Ok((state, Some(found)))
+}
+
+/// Where the pre-migration copy of a state file in schema `version` goes.
+fn backup_path(statefile: &Path, version: u32) -> PathBuf {
+    let mut name = statefile.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{version}.bak"));
+    statefile.with_file_name(name)
+}
+
+/// Version 2 introduces the `schema_version` field itself, which `upgrade`
+/// stamps on after every step, so there is nothing else to do here.
+#[allow(clippy::unnecessary_wraps)]
+fn v1_to_v2(value: Value) -> Result<Value> {
+    Ok(value)
+}
+
+/// Version 3 puts a ledger behind each queue's balance. Whatever a queue had
+/// accumulated so far is carried over as an opening adjustment, so the ledger
+/// adds up to the balance from the start.
+#[allow(clippy::unnecessary_wraps)]
+fn v2_to_v3(mut value: Value) -> Result<Value> {
+    if let Some(queues) = value["queues"].as_array_mut() {
+        for queue in queues {
+            queue["ledger"] = json!([{
+                "time": queue["last_calculation"],
+                "kind": "adjustment",
+                "amount": queue["current_balance"],
+                "reason": "Opening balance, carried over from before the ledger existed",
+            }]);
+        }
+    }
+
+    Ok(value)
+}
+
+/// Version 4 replaces the `paused` flag with the intervals a queue spent
+/// paused. A paused queue has been settled up to its last calculation, so
+/// that's where its pause is taken to start.
+#[allow(clippy::unnecessary_wraps)]
+fn v3_to_v4(mut value: Value) -> Result<Value> {
+    if let Some(queues) = value["queues"].as_array_mut() {
+        for queue in queues {
+            let paused = queue["paused"].as_bool().unwrap_or(false);
+            queue["pauses"] = if paused {
+                json!([{ "from": queue["last_calculation"], "until": null }])
+            } else {
+                json!([])
+            };
+            if let Some(queue) = queue.as_object_mut() {
+                queue.remove("paused");
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+/// Version 5 gives every item a stable ID, so commands can refer to items
+/// other than the head of the queue.
+#[allow(clippy::unnecessary_wraps)]
+fn v4_to_v5(mut value: Value) -> Result<Value> {
+    let mut taken = HashSet::new();
+    if let Some(queues) = value["queues"].as_array_mut() {
+        for queue in queues {
+            for list in ["future_purchases", "past_purchases"] {
+                if let Some(items) = queue[list].as_array_mut() {
+                    for item in items {
+                        let id = new_item_id(&taken);
+                        item["id"] = Value::from(id.clone());
+                        taken.insert(id);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(value)
+}
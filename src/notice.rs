@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+/// Something worth telling the person using sq that isn't an error, ie that
+/// the state file was migrated. The library never prints these itself, they
+/// are handed to whatever the session was given with `Session::with_notify`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notice {
+    /// There was no state at `path` yet, so a fresh one was written there.
+    Fresh { path: PathBuf },
+    /// The state at `path` was brought forward from schema version `from` to
+    /// `to`, see `migrations::upgrade`.
+    Migrated { path: PathBuf, from: u32, to: u32 },
+    /// The JSON state file `from` was imported into the database `into`.
+    Imported { from: PathBuf, into: PathBuf },
+    /// The undo history at `path` couldn't be used and was dropped.
+    JournalDropped { path: PathBuf, reason: String },
+}
+
+/// Receives notices, see `Notice`.
+pub type Notify<'a> = &'a dyn Fn(&Notice);
+
+/// Ignores every notice, for loading state nobody needs to hear about, ie a
+/// scratch copy of a backup.
+pub fn ignore(_: &Notice) {}
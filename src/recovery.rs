@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::Local;
+
+use crate::error::Result;
+use crate::error::SqError;
+use crate::migrations;
+use crate::storage::JsonStorage;
+use crate::storage::Storage;
+use crate::types::State;
+
+/// Deal with a state file that can't be parsed, either as JSON or as any known
+/// version of the state schema. The file is never replaced with a default
+/// state. Instead, a timestamped copy of it is put aside and the returned
+/// error points at the most recent backup, so the caller can offer to
+/// `restore` it.
+#[must_use]
+pub fn quarantine_unreadable(statefile: &Path, content: &str, err: &SqError) -> SqError {
+    let quarantined = match quarantine(statefile, content) {
+        Ok(path) => path,
+        Err(err) => return err,
+    };
+    let backup = match latest_backup(statefile) {
+        Ok(backup) => backup,
+        Err(err) => return err,
+    };
+
+    SqError::Corrupt {
+        path: statefile.to_path_buf(),
+        reason: err.to_string(),
+        quarantined,
+        backup,
+    }
+}
+
+/// Replace the state file with `backup`, brought forward to the current
+/// schema like any other state file.
+pub fn restore(statefile: &Path, backup: &Path) -> Result<State> {
+    let store = JsonStorage::new(statefile.to_path_buf());
+    let _lock = store.lock()?;
+    let (state, _) = migrations::upgrade(&fs::read_to_string(backup)?, statefile)?;
+    store.save(&state)?;
+    Ok(state)
+}
+
+/// Copy the unreadable state file to `state.json.corrupt-<timestamp>`. If the
+/// most recent quarantined copy has the same contents, that one is reused, so
+/// a shell running `sq status` on every startup doesn't pile up copies.
+fn quarantine(statefile: &Path, content: &str) -> Result<PathBuf> {
+    let prefix = sibling_name(statefile, ".corrupt-");
+    let mut existing = siblings_starting_with(statefile, &prefix)?;
+    existing.sort();
+
+    if let Some(last) = existing.last() {
+        if fs::read_to_string(last).is_ok_and(|c| c == content) {
+            return Ok(last.clone());
+        }
+    }
+
+    let name = format!("{}{}", prefix, Local::now().format("%Y%m%dT%H%M%S"));
+    let target = statefile.with_file_name(name);
+    fs::write(&target, content)?;
+    Ok(target)
+}
+
+/// Most recently written backup of the state file, if any. Both the copies
+/// left by migrations and the rotating backups in `backups/` count.
+fn latest_backup(statefile: &Path) -> Result<Option<PathBuf>> {
+    let mut candidates: Vec<PathBuf> =
+        siblings_starting_with(statefile, &sibling_name(statefile, ".v"))?
+            .into_iter()
+            .filter(|path| path.to_string_lossy().ends_with(".bak"))
+            .collect();
+
+    if let (Some(dir), Some(stem)) = (statefile.parent(), statefile.file_stem()) {
+        let rotating = dir.join("backups");
+        if rotating.is_dir() {
+            let prefix = format!("{}-", stem.to_string_lossy());
+            for entry in fs::read_dir(rotating)? {
+                let path = entry?.path();
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if name.starts_with(&prefix) && path.extension() == statefile.extension() {
+                    candidates.push(path);
+                }
+            }
+        }
+    }
+
+    let mut backups = Vec::new();
+    for path in candidates {
+        backups.push((fs::metadata(&path)?.modified()?, path));
+    }
+    backups.sort();
+
+    Ok(backups.pop().map(|(_, path)| path))
+}
+
+fn sibling_name(statefile: &Path, suffix: &str) -> String {
+    format!(
+        "{}{}",
+        statefile.file_name().unwrap_or_default().to_string_lossy(),
+        suffix
+    )
+}
+
+fn siblings_starting_with(statefile: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    let Some(dir) = statefile.parent() else {
+        return Ok(Vec::new());
+    };
+
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            found.push(entry.path());
+        }
+    }
+
+    Ok(found)
+}
@@ -0,0 +1,249 @@
+use std::fs;
+use std::path::Path;
+
+use crate::backups::Backups;
+use crate::clock::Clock;
+use crate::clock::SystemClock;
+use crate::config;
+use crate::config::Config;
+use crate::config::Location;
+use crate::engine;
+use crate::error::Result;
+use crate::error::SqError;
+use crate::journal::Journal;
+use crate::notice;
+use crate::notice::Notice;
+use crate::notice::Notify;
+use crate::storage;
+use crate::storage::Storage;
+use crate::types::Queue;
+use crate::types::State;
+
+/// Where the state lives and how it is kept. Everything that reads or changes
+/// the state goes through here, so locking, backups and the undo journal are
+/// taken care of the same way for every caller.
+pub struct Session {
+    location: Location,
+    config: Config,
+    clock: Box<dyn Clock>,
+    /// Queue to act on instead of the selected one, see `with_queue`.
+    queue: Option<String>,
+    /// Hears about anything worth telling, see `with_notify`.
+    notify: Box<dyn Fn(&Notice)>,
+}
+
+impl Session {
+    /// Find the state and read the config that goes with it. `state` is an
+    /// explicitly given location, see `config::locate` for how it ranks
+    /// against the environment.
+    pub fn open(state: Option<&Path>) -> Result<Self> {
+        let location = config::locate(state)?;
+        fs::create_dir_all(location.dir())?;
+        let config = config::read_config(location.dir())?;
+
+        Ok(Self {
+            location,
+            config,
+            clock: Box::new(SystemClock),
+            queue: None,
+            notify: Box::new(notice::ignore),
+        })
+    }
+
+    /// Use `clock` instead of the system time for everything done through
+    /// this session.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Act on the queue `name` instead of the one selected in the state,
+    /// without changing the selection.
+    #[must_use]
+    pub fn with_queue(mut self, name: impl Into<String>) -> Self {
+        self.queue = Some(name.into());
+        self
+    }
+
+    /// Hand notices to `notify`, ie to print them. Without one, they are
+    /// dropped.
+    #[must_use]
+    pub fn with_notify(mut self, notify: impl Fn(&Notice) + 'static) -> Self {
+        self.notify = Box::new(notify);
+        self
+    }
+
+    /// Name of the queue this session acts on, see `with_queue`.
+    #[must_use]
+    pub fn queue_name<'a>(&'a self, state: &'a State) -> &'a str {
+        self.queue.as_deref().unwrap_or(&state.currently_selected)
+    }
+
+    #[must_use]
+    pub fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    pub fn notify(&self) -> Notify<'_> {
+        &*self.notify
+    }
+
+    #[must_use]
+    pub fn location(&self) -> &Location {
+        &self.location
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Open the storage backend selected for this location.
+    pub fn storage(&self) -> Result<Box<dyn Storage>> {
+        storage::open(&self.location, &self.config, self.notify())
+    }
+
+    /// The rotating backups of this state, as configured.
+    #[must_use]
+    pub fn backups(&self) -> Backups {
+        Backups::new(self.location.dir(), self.config.backups.clone())
+    }
+
+    /// Read the state, as of now, see `settle`. The storage is locked while
+    /// reading, since loading writes back a fresh or migrated state.
+    pub fn load(&self) -> Result<State> {
+        let store = self.storage()?;
+        let mut state = {
+            let _lock = store.lock()?;
+            self.load_from(&*store)?
+        };
+        self.settle(&mut state)?;
+        Ok(state)
+    }
+
+    /// Load the state from `store`, starting out fresh if there is none yet.
+    /// Should be called while holding the storage lock.
+    fn load_from(&self, store: &dyn Storage) -> Result<State> {
+        if let Some(state) = store.load(self.notify())? {
+            return Ok(state);
+        }
+
+        let state = State::new(self.clock().now());
+        store.save(&state)?;
+        (self.notify)(&Notice::Fresh {
+            path: store.path().to_path_buf(),
+        });
+        Ok(state)
+    }
+
+    /// Bring `state` up to now: apply the queues' policies and allocate the
+    /// shared income, see `engine::enforce_policies` and `engine::allocate`.
+    fn settle(&self, state: &mut State) -> Result<()> {
+        engine::enforce_policies(state, self.clock())?;
+        engine::allocate(state, self.clock())
+    }
+
+    /// The queue this session acts on, usually the selected one.
+    pub fn selected_queue(&self) -> Result<Queue> {
+        let state = self.load()?;
+        state.queue(self.queue_name(&state)).cloned()
+    }
+
+    /// Get ready to change the state: lock the storage, load the state and
+    /// take a backup if one is due. The lock is held until the returned file
+    /// is dropped.
+    pub fn begin_change(&self) -> Result<(Box<dyn Storage>, fs::File, State)> {
+        let store = self.storage()?;
+        let lock = store.lock()?;
+        let mut state = self.load_from(&*store)?;
+        self.settle(&mut state)?;
+        self.backups().take(&*store, self.clock())?;
+
+        Ok((store, lock, state))
+    }
+
+    /// Lock the storage, read the state, let `f` modify it and write it back.
+    /// Concurrent invocations of sq are serialized by the lock, so no update
+    /// gets lost. If `f` fails, nothing is written. The state as it was before
+    /// is kept in the journal, so `sq undo` can take back `action`. The state
+    /// is settled again afterwards, in case balances changed.
+    pub fn update_state<T, F>(&self, action: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut State) -> Result<T>,
+    {
+        let (store, _lock, mut state) = self.begin_change()?;
+        let before = state.clone();
+        let res = f(&mut state)?;
+        self.settle(&mut state)?;
+        store.save(&state)?;
+        self.record_in_journal(&*store, action, (&before, &state))?;
+
+        Ok(res)
+    }
+
+    /// Replace the whole state with `state`, ie to restore a backup. Unlike
+    /// `update_state`, this works even if the current state can't be parsed.
+    /// It can't be backed up or journaled then, but loading it already put a
+    /// copy aside, see `recovery::quarantine_unreadable`.
+    pub fn replace_state(&self, action: &str, mut state: State) -> Result<()> {
+        let store = self.storage()?;
+        let _lock = store.lock()?;
+        let before = match self.load_from(&*store) {
+            Ok(mut before) => {
+                self.settle(&mut before)?;
+                self.backups().take(&*store, self.clock())?;
+                Some(before)
+            }
+            Err(SqError::Corrupt { .. }) => None,
+            Err(err) => return Err(err),
+        };
+
+        self.settle(&mut state)?;
+        store.save(&state)?;
+        if let Some(before) = before {
+            self.record_in_journal(&*store, action, (&before, &state))?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `update_state`, but only hands the queue this session acts on to
+    /// `f`, usually the selected one.
+    pub fn update_selected_queue<T, F>(&self, action: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Queue) -> Result<T>,
+    {
+        let (store, _lock, mut state) = self.begin_change()?;
+        let before = state.clone();
+        let current_name = self.queue_name(&state).to_string();
+        let queue = state.queue_mut(&current_name)?;
+        let res = f(queue)?;
+        self.settle(&mut state)?;
+        let passes_money_on = state
+            .queues
+            .iter()
+            .any(|q| q.policy.targets().next().is_some());
+        if state.shared_income.is_some() || passes_money_on {
+            // Settling can change any of the queues sharing the income, or
+            // the ones policies move money into.
+            store.save(&state)?;
+        } else {
+            store.save_queue(&state, &current_name)?;
+        }
+        self.record_in_journal(&*store, action, (&before, &state))?;
+
+        Ok(res)
+    }
+
+    /// Remember `before` as the state prior to `action`, which left it as
+    /// `after`. Should be called while holding the storage lock.
+    fn record_in_journal(
+        &self,
+        store: &dyn Storage,
+        action: &str,
+        (before, after): (&State, &State),
+    ) -> Result<()> {
+        Journal::open(store).record(action, (before, after), self.clock(), self.notify())
+    }
+}
@@ -0,0 +1,121 @@
+mod json;
+mod sqlite;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use fs2::FileExt;
+
+use crate::config::Backend;
+use crate::config::Config;
+use crate::config::Location;
+use crate::error::Result;
+use crate::error::SqError;
+use crate::notice::Notice;
+use crate::notice::Notify;
+use crate::types::State;
+
+pub use json::JsonStorage;
+pub use sqlite::SqliteStorage;
+
+/// Somewhere to keep the state between invocations. All persistence goes
+/// through this, so the rest of sq doesn't care whether the state lives in a
+/// JSON file or a database.
+pub trait Storage {
+    /// The file backing this storage.
+    fn path(&self) -> &Path;
+
+    /// Load all queues, their items and purchase history, migrating them to
+    /// the current schema if needed. A migrated state is written back right
+    /// away, so this should be called while holding the lock, see `lock`, and
+    /// `notify` hears about it. `None` if nothing was stored yet.
+    fn load(&self, notify: Notify) -> Result<Option<State>>;
+
+    /// Persist all of `state`.
+    fn save(&self, state: &State) -> Result<()>;
+
+    /// Persist changes to the queue called `name`, its items and purchase
+    /// history, leaving other queues alone. `state` has to be the state the
+    /// queue is part of, backends that can't write a single queue on its own
+    /// fall back to writing all of it.
+    fn save_queue(&self, state: &State, _name: &str) -> Result<()> {
+        self.save(state)
+    }
+
+    /// Hold an exclusive advisory lock on the storage for as long as the
+    /// returned handle lives. Every read-modify-write cycle has to go through
+    /// this, so concurrent invocations of sq don't lose each other's updates.
+    fn lock(&self) -> Result<fs::File> {
+        let mut path = self.path().to_path_buf().into_os_string();
+        path.push(".lock");
+        let lockfile = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.lock_exclusive().map(|()| f))
+            .map_err(|err| {
+                SqError::Storage(format!("Can't lock {}: {err}", path.to_string_lossy()))
+            })?;
+
+        Ok(lockfile)
+    }
+}
+
+/// Open the storage at `location`. An explicitly given state file picks its
+/// backend by extension, otherwise the one selected in `config` is used.
+/// Switching a directory to SQLite imports the JSON state file on first use.
+pub fn open(location: &Location, config: &Config, notify: Notify) -> Result<Box<dyn Storage>> {
+    let dir = match location {
+        Location::File(path) => {
+            return match path.extension().and_then(|e| e.to_str()) {
+                Some("db" | "sqlite" | "sqlite3") => Ok(Box::new(SqliteStorage::new(path.clone()))),
+                _ => Ok(Box::new(JsonStorage::new(path.clone()))),
+            }
+        }
+        Location::Dir(dir) => dir,
+    };
+    let json = JsonStorage::new(dir.join("state.json"));
+
+    match config.storage {
+        Backend::Json => Ok(Box::new(json)),
+        Backend::Sqlite => {
+            let db = SqliteStorage::new(dir.join("state.db"));
+            if !db.path().exists() && json.path().exists() {
+                let _lock = json.lock()?;
+                let _db_lock = db.lock()?;
+                // Another sq may have imported it while we waited for the lock.
+                if db.path().exists() {
+                    return Ok(Box::new(db));
+                }
+                if let Some(state) = json.load(notify)? {
+                    db.save(&state)?;
+                    notify(&Notice::Imported {
+                        from: json.path().to_path_buf(),
+                        into: db.path().to_path_buf(),
+                    });
+                }
+            }
+            Ok(Box::new(db))
+        }
+    }
+}
+
+/// Write `contents` to a temporary file next to `path` and move it into place,
+/// so `path` is either completely old or completely new, even if sq dies
+/// halfway through writing.
+pub fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(format!(".tmp.{}", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+    write().map_err(|err| SqError::Storage(format!("Can't write {}: {err}", path.display())))
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::error::SqError;
+use crate::migrations;
+use crate::notice::Notice;
+use crate::notice::Notify;
+use crate::recovery;
+use crate::storage;
+use crate::storage::Storage;
+use crate::types::State;
+
+/// The whole state as a single pretty-printed JSON file.
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl JsonStorage {
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Storage for JsonStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read and parse the state file, migrating it to the current schema if
+    /// needed. A state file that can't be parsed is never silently replaced,
+    /// see `recovery::quarantine_unreadable`.
+    fn load(&self, notify: Notify) -> Result<Option<State>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&self.path).map_err(|err| {
+            SqError::Storage(format!(
+                "Can't read state file {}: {err}",
+                self.path.display()
+            ))
+        })?;
+
+        match migrations::upgrade(&content, &self.path) {
+            Ok((state, migrated)) => {
+                if let Some(from) = migrated {
+                    self.save(&state)?;
+                    notify(&Notice::Migrated {
+                        path: self.path.clone(),
+                        from,
+                        to: state.schema_version,
+                    });
+                }
+                Ok(Some(state))
+            }
+            Err(err @ SqError::Parse(_)) => {
+                Err(recovery::quarantine_unreadable(&self.path, &content, &err))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Written atomically, see `storage::write_atomically`.
+    fn save(&self, state: &State) -> Result<()> {
+        storage::write_atomically(&self.path, &serde_json::to_string_pretty(state)?)
+    }
+}
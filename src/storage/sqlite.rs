@@ -0,0 +1,226 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::Transaction;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::Result;
+use crate::migrations;
+use crate::notice::Notice;
+use crate::notice::Notify;
+use crate::storage::Storage;
+use crate::types::Queue;
+use crate::types::State;
+
+/// Lists in a queue that are stored one row per element, so that writing a
+/// queue only touches the rows that actually changed.
+const ITEM_LISTS: [&str; 3] = ["future_purchases", "past_purchases", "ledger"];
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS queues (
+    name TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS items (
+    queue TEXT NOT NULL,
+    list TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (queue, list, position)
+);
+";
+
+/// State kept in an embedded SQLite database. Rows hold the JSON encoding of
+/// the same types the JSON backend writes, so both share one set of
+/// migrations, but a queue, and each of its items, can be written on its own.
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn load(&self, notify: Notify) -> Result<Option<State>> {
+        let conn = self.connect()?;
+        let meta: Option<String> = conn
+            .query_row("SELECT value FROM meta WHERE key = 'state'", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let Some(meta) = meta else {
+            return Ok(None);
+        };
+
+        let mut state: Value = serde_json::from_str(&meta)?;
+        let mut queues = Vec::new();
+        let mut stmt = conn.prepare("SELECT name, data FROM queues ORDER BY position")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (name, data) = row?;
+            let mut queue: Value = serde_json::from_str(&data)?;
+            for list in ITEM_LISTS {
+                queue[list] = Value::Array(read_items(&conn, &name, list)?);
+            }
+            queues.push(queue);
+        }
+        state["queues"] = Value::Array(queues);
+
+        let (state, migrated) = migrations::upgrade(&state.to_string(), &self.path)?;
+        if let Some(from) = migrated {
+            self.save(&state)?;
+            notify(&Notice::Migrated {
+                path: self.path.clone(),
+                from,
+                to: state.schema_version,
+            });
+        }
+
+        Ok(Some(state))
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        write_state(&tx, state)?;
+        Ok(tx.commit()?)
+    }
+
+    /// Falls back to writing all of `state` if its queues aren't the stored
+    /// ones anymore, ie because the queue was renamed.
+    fn save_queue(&self, state: &State, name: &str) -> Result<()> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let names: Vec<&str> = state.queues.iter().map(|q| q.name.as_str()).collect();
+        match names.iter().position(|n| *n == name) {
+            Some(position) if stored_queues(&tx)? == names => {
+                write_queue(&tx, position, &state.queues[position])?;
+            }
+            _ => write_state(&tx, state)?,
+        }
+        Ok(tx.commit()?)
+    }
+}
+
+/// Write all of `state`, dropping the rows of queues it doesn't have anymore.
+fn write_state(tx: &Transaction, state: &State) -> Result<()> {
+    let mut meta = serde_json::to_value(state)?;
+    strip(&mut meta, &["queues"]);
+    tx.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('state', ?1)",
+        params![meta.to_string()],
+    )?;
+
+    let names: Vec<&str> = state.queues.iter().map(|q| q.name.as_str()).collect();
+    for name in stored_queues(tx)? {
+        if !names.contains(&name.as_str()) {
+            tx.execute("DELETE FROM queues WHERE name = ?1", params![name])?;
+            tx.execute("DELETE FROM items WHERE queue = ?1", params![name])?;
+        }
+    }
+
+    for (position, queue) in state.queues.iter().enumerate() {
+        write_queue(tx, position, queue)?;
+    }
+    Ok(())
+}
+
+/// Names of the stored queues, in order.
+fn stored_queues(tx: &Transaction) -> Result<Vec<String>> {
+    let mut stmt = tx.prepare("SELECT name FROM queues ORDER BY position")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+fn read_items(conn: &Connection, queue: &str, list: &str) -> Result<Vec<Value>> {
+    let mut stmt =
+        conn.prepare("SELECT data FROM items WHERE queue = ?1 AND list = ?2 ORDER BY position")?;
+    let rows = stmt.query_map(params![queue, list], |row| row.get::<_, String>(0))?;
+
+    let mut items = Vec::new();
+    for data in rows {
+        items.push(serde_json::from_str(&data?)?);
+    }
+    Ok(items)
+}
+
+/// Write a queue and bring its item rows in line with it, leaving rows that
+/// haven't changed untouched. Purchase history only ever grows at the end, so
+/// this keeps writes small no matter how long it gets.
+fn write_queue(tx: &Transaction, position: usize, queue: &Queue) -> Result<()> {
+    let mut data = serde_json::to_value(queue)?;
+    strip(&mut data, &ITEM_LISTS);
+    tx.execute(
+        "INSERT OR REPLACE INTO queues (name, position, data) VALUES (?1, ?2, ?3)",
+        params![queue.name, position, data.to_string()],
+    )?;
+
+    let rows = [
+        rows(&queue.future_purchases)?,
+        rows(&queue.past_purchases)?,
+        rows(&queue.ledger)?,
+    ];
+    for (list, items) in ITEM_LISTS.iter().zip(rows) {
+        let stored: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT data FROM items WHERE queue = ?1 AND list = ?2 ORDER BY position",
+            )?;
+            let rows = stmt.query_map(params![queue.name, list], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        for (i, data) in items.iter().enumerate() {
+            if stored.get(i) != Some(data) {
+                tx.execute(
+                    "INSERT OR REPLACE INTO items (queue, list, position, data) VALUES (?1, ?2, ?3, ?4)",
+                    params![queue.name, list, i, data],
+                )?;
+            }
+        }
+        tx.execute(
+            "DELETE FROM items WHERE queue = ?1 AND list = ?2 AND position >= ?3",
+            params![queue.name, list, items.len()],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn rows<'a, T: Serialize + 'a>(list: impl IntoIterator<Item = &'a T>) -> Result<Vec<String>> {
+    list.into_iter()
+        .map(|item| Ok(serde_json::to_string(item)?))
+        .collect()
+}
+
+fn strip(value: &mut Value, keys: &[&str]) {
+    if let Some(obj) = value.as_object_mut() {
+        for key in keys {
+            obj.remove(*key);
+        }
+    }
+}
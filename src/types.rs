@@ -1,29 +1,217 @@
+use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::fmt;
+use std::hash::BuildHasher;
 
+use chrono::DateTime;
+use chrono::Local;
 use fraction::GenericDecimal;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+use crate::error::Result;
+use crate::error::SqError;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+
 /// Type used for money, abstracting over an arbitrary-precision number. This is
 /// important, as sq has to work correctly on potentially very small fractions
 /// of currency without loss of precision, as the accrual window has to be kept
 /// to.
 pub type M = GenericDecimal<u64, u8>;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Item {
+    /// Short, stable handle to refer to the item by, see `new_item_id`.
+    pub id: String,
     pub name: String,
     pub amount: M,
     pub purchase_link: Option<String>,
     pub time_purchased: Option<String>,
+    /// Anything worth remembering about the item, ie which size or color.
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Free-form labels, ie `books` or `gift`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Every time the price changed, oldest first. `amount` is always the
+    /// latest price.
+    #[serde(default)]
+    pub price_history: Vec<PriceChange>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The price of an item changed from `from` to `to` at `time`, an RFC 2822
+/// timestamp.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PriceChange {
+    pub time: String,
+    pub from: M,
+    pub to: M,
+}
+
+impl Item {
+    /// Change the price at `time`, keeping the old one in `price_history`.
+    pub fn set_price(&mut self, price: M, time: DateTime<Local>) {
+        if price == self.amount {
+            return;
+        }
+        self.price_history.push(PriceChange {
+            time: time.to_rfc2822(),
+            from: self.amount,
+            to: price,
+        });
+        self.amount = price;
+    }
+}
+
+/// Letters item IDs are made of. No digits, so an ID can't be mistaken for a
+/// position in the queue, and no vowels, so they don't spell anything.
+const ID_ALPHABET: &[u8] = b"bcdfghjklmnpqrstvwxz";
+const ID_LENGTH: usize = 4;
+
+/// A random item ID that isn't in `taken` yet.
+#[must_use]
+pub fn new_item_id<S: BuildHasher>(taken: &HashSet<String, S>) -> String {
+    let mut rng = rand::thread_rng();
+    loop {
+        let id: String = (0..ID_LENGTH)
+            .map(|_| char::from(ID_ALPHABET[rng.gen_range(0..ID_ALPHABET.len())]))
+            .collect();
+        if !taken.contains(&id) {
+            return id;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Income {
     pub amount: f64,
     pub interval_in_days: u64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// What caused a change to a queue's balance.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// Income accrued over time.
+    Accrual,
+    /// An item was bought from the balance.
+    Purchase,
+    /// An item was bought with `--force`, pushing the balance below zero.
+    Overdraft,
+    /// The balance was changed by hand, or carried over from before the
+    /// ledger existed.
+    Adjustment,
+    /// The income changed. Doesn't move the balance, but explains why accrual
+    /// changed pace.
+    BudgetChange,
+    /// Money put in by hand, see `engine::deposit`.
+    Deposit,
+    /// Money taken out by hand, see `engine::withdraw`.
+    Withdrawal,
+    /// Money moved to or from another queue, see `engine::transfer`.
+    Transfer,
+    /// Money that was kept too long and is gone, see `Expiry`.
+    Expiry,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            EntryKind::Accrual => "accrual",
+            EntryKind::Purchase => "purchase",
+            EntryKind::Overdraft => "overdraft",
+            EntryKind::Adjustment => "adjustment",
+            EntryKind::BudgetChange => "budget change",
+            EntryKind::Deposit => "deposit",
+            EntryKind::Withdrawal => "withdrawal",
+            EntryKind::Transfer => "transfer",
+            EntryKind::Expiry => "expiry",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A single change to a queue's balance. Entries are only ever appended, the
+/// balance is their sum.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub time: String,
+    pub kind: EntryKind,
+    /// Signed change to the balance.
+    pub amount: M,
+    pub reason: String,
+    /// When accrual that ends at `time` started, so expiry can tell how old
+    /// that money is. Anything else happened at `time` alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+}
+
+/// A stretch of time during which nothing accrues, from `from` until `until`,
+/// or indefinitely while `until` is `None`. Both are RFC 2822 timestamps.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Pause {
+    pub from: String,
+    pub until: Option<String>,
+    /// Put there by pausing every queue at once, see `State::globally_paused`.
+    #[serde(default)]
+    pub global: bool,
+}
+
+/// Keeps money from piling up in a queue, so it gets spent. See
+/// `engine::enforce_policies`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    pub cap: Option<Cap>,
+    pub expiry: Option<Expiry>,
+    #[serde(default)]
+    pub overdraft: Overdraft,
+}
+
+/// The most the balance grows to by accruing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cap {
+    pub amount: f64,
+    /// Queue that gets whatever is over the cap. Without one, accrual stops
+    /// at the cap.
+    pub spill_to: Option<String>,
+}
+
+/// Use it or lose it: money kept longer than `after_days` is gone, spending
+/// the oldest money first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Expiry {
+    pub after_days: u32,
+    /// Queue that gets the expired money, instead of it being gone.
+    pub sweep_to: Option<String>,
+}
+
+/// How far below zero the balance may go when forced, ie by `sq buy
+/// --force`. Without forcing it, nothing goes below zero either way.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Overdraft {
+    Forbid,
+    /// Down to a debt of `limit`.
+    UpTo {
+        limit: f64,
+    },
+    #[default]
+    Unlimited,
+}
+
+impl Policy {
+    /// Names of the queues this policy moves money into.
+    pub fn targets(&self) -> impl Iterator<Item = &str> {
+        let spill_to = self.cap.as_ref().and_then(|cap| cap.spill_to.as_deref());
+        let sweep_to = self
+            .expiry
+            .as_ref()
+            .and_then(|expiry| expiry.sweep_to.as_deref());
+        spill_to.into_iter().chain(sweep_to)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Queue {
     pub income: Income,
     pub name: String,
@@ -31,31 +219,180 @@ pub struct Queue {
     pub current_balance: M,
     pub future_purchases: VecDeque<Item>,
     pub past_purchases: VecDeque<Item>,
-    pub paused: bool,
+    /// Every time the queue was paused, oldest first.
+    pub pauses: Vec<Pause>,
+    /// Put away, see `engine::archive_queue`.
+    #[serde(default)]
+    pub archived: bool,
+    /// Limits on how much money the queue holds on to.
+    #[serde(default)]
+    pub policy: Policy,
+    /// Every change to `current_balance`, oldest first.
+    pub ledger: Vec<LedgerEntry>,
+}
+
+impl Queue {
+    /// An empty queue with an income of $1 per day, accruing from `now`.
+    #[must_use]
+    pub fn new(name: String, now: DateTime<Local>) -> Self {
+        Self {
+            income: Income {
+                amount: 1.0,
+                interval_in_days: 1,
+            },
+            name,
+            last_calculation: now.to_rfc2822(),
+            current_balance: 0.into(),
+            future_purchases: VecDeque::new(),
+            past_purchases: VecDeque::new(),
+            pauses: Vec::new(),
+            archived: false,
+            policy: Policy::default(),
+            ledger: Vec::new(),
+        }
+    }
+
+    /// Append an entry to the ledger, made at `time`, and apply it to the
+    /// balance. This is the only way the balance should ever change.
+    pub fn record(&mut self, time: DateTime<Local>, kind: EntryKind, amount: M, reason: String) {
+        self.current_balance += amount;
+        self.ledger.push(LedgerEntry {
+            time: time.to_rfc2822(),
+            kind,
+            amount,
+            reason,
+            since: None,
+        });
+    }
+
+    /// Like `record`, for income that accrued from `since` until `time`.
+    pub fn record_accrual(
+        &mut self,
+        (since, time): (DateTime<Local>, DateTime<Local>),
+        amount: M,
+        reason: String,
+    ) {
+        self.record(time, EntryKind::Accrual, amount, reason);
+        if let Some(entry) = self.ledger.last_mut() {
+            entry.since = Some(since.to_rfc2822());
+        }
+    }
+
+    /// IDs of every item in the queue, bought or not. Item IDs are unique
+    /// within their queue.
+    #[must_use]
+    pub fn item_ids(&self) -> HashSet<String> {
+        self.future_purchases
+            .iter()
+            .chain(&self.past_purchases)
+            .map(|item| item.id.clone())
+            .collect()
+    }
+
+    /// The balance as derived from the ledger. Matches `current_balance`
+    /// unless someone edited the state by hand.
+    #[must_use]
+    pub fn ledger_balance(&self) -> M {
+        self.ledger.iter().map(|entry| entry.amount).sum()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct State {
+    /// Version of the on-disk layout, see `migrations` for how older files are
+    /// brought forward.
+    pub schema_version: u32,
     pub queues: Vec<Queue>,
     /// Identify queue by its name. Not foolproof, good enough here.
     pub currently_selected: String,
+    /// Whether every queue is paused, see `engine::pause_all`.
     pub globally_paused: bool,
+    /// Income split between several queues, instead of each having its own.
+    #[serde(default)]
+    pub shared_income: Option<SharedIncome>,
 }
 
-impl Default for State {
-    fn default() -> Self {
+/// One income for several queues, divided between them according to `rule`,
+/// see `engine::allocate`. Queues without a share keep their own income.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SharedIncome {
+    pub income: Income,
+    pub rule: AllocationRule,
+    pub shares: Vec<Share>,
+}
+
+/// How a shared income is divided between queues.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationRule {
+    /// In proportion to each share's `value`, ie 2 and 1 split it two to one.
+    Weights,
+    /// Each share's `value` is a fixed amount per interval. Whatever isn't
+    /// given out isn't saved anywhere.
+    Fixed,
+    /// All of it goes to the first queue, until its balance reaches the
+    /// share's `value`. Anything above that spills over into the next one,
+    /// and so on. The last queue keeps whatever is left, cap or not.
+    Waterfall,
+}
+
+impl fmt::Display for AllocationRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AllocationRule::Weights => "weight",
+            AllocationRule::Fixed => "fixed amounts",
+            AllocationRule::Waterfall => "waterfall",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A queue's part of a shared income. What `value` means depends on the
+/// `AllocationRule`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Share {
+    pub queue: String,
+    pub value: f64,
+}
+
+impl State {
+    /// A brand new state with a single queue called `default`, accruing
+    /// from `now`.
+    #[must_use]
+    pub fn new(now: DateTime<Local>) -> Self {
         Self {
-            queues: vec![Queue {
-                income: Income{ amount: 1.0, interval_in_days: 1 },
-                name: "default".into(),
-                last_calculation: chrono::Local::now().to_rfc2822(),
-                current_balance: 0.into(),
-                future_purchases: VecDeque::new(),
-                past_purchases: VecDeque::new(),
-                paused: false,
-            }],
+            schema_version: CURRENT_SCHEMA_VERSION,
+            queues: vec![Queue::new("default".into(), now)],
             currently_selected: "default".into(),
             globally_paused: false,
+            shared_income: None,
         }
     }
+
+    /// The queue commands act on, see `currently_selected`.
+    pub fn selected_queue(&self) -> Result<&Queue> {
+        self.queue(&self.currently_selected)
+    }
+
+    pub fn queue(&self, name: &str) -> Result<&Queue> {
+        self.queues
+            .iter()
+            .find(|q| q.name == name)
+            .ok_or_else(|| SqError::MissingQueue(name.to_string()))
+    }
+
+    /// The share of the shared income the queue `name` gets, if any.
+    #[must_use]
+    pub fn share(&self, name: &str) -> Option<&Share> {
+        self.shared_income
+            .as_ref()
+            .and_then(|shared| shared.shares.iter().find(|share| share.queue == name))
+    }
+
+    pub fn queue_mut(&mut self, name: &str) -> Result<&mut Queue> {
+        self.queues
+            .iter_mut()
+            .find(|q| q.name == name)
+            .ok_or_else(|| SqError::MissingQueue(name.to_string()))
+    }
 }
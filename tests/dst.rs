@@ -0,0 +1,46 @@
+//! Accrual across the changes to and from daylight saving time. A test binary
+//! of its own, since the time zone has to be set before anything reads it.
+
+use chrono::prelude::*;
+use fraction::ToPrimitive;
+
+use sq::clock::Clock;
+use sq::clock::FakeClock;
+use sq::engine;
+use sq::State;
+
+/// Berlin, spelled out so it works without a time zone database.
+const BERLIN: &str = "CET-1CEST,M3.5.0,M10.5.0/3";
+
+fn balance(state: &State, clock: &FakeClock) -> f64 {
+    let queue = state.queue("default").unwrap();
+    let (_, balance) = engine::calculate_current_amount(queue, clock).unwrap();
+    balance.to_f64().unwrap()
+}
+
+#[test]
+fn accrues_real_time_across_dst() {
+    std::env::set_var("TZ", BERLIN);
+    let noon = |month, day| Local.ymd(2026, month, day).and_hms(12, 0, 0);
+    assert_eq!(noon(3, 28).offset().local_minus_utc(), 3600);
+    assert_eq!(noon(3, 29).offset().local_minus_utc(), 7200);
+
+    // Noon to noon is only 23 hours when the clocks go forward.
+    let clock = FakeClock::new(noon(3, 28));
+    let mut state = State::new(clock.now());
+    clock.set(noon(3, 29));
+    assert!((balance(&state, &clock) - 23.0 / 24.0).abs() < 0.005);
+
+    // Settling stores summer time, which has to read back the same.
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    clock.set(noon(3, 30));
+    assert!((balance(&state, &clock) - 47.0 / 24.0).abs() < 0.005);
+
+    // And 25 hours when they go back.
+    clock.set(noon(10, 24));
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    let before = balance(&state, &clock);
+    clock.set(noon(10, 25));
+    assert_eq!(noon(10, 25).offset().local_minus_utc(), 3600);
+    assert!((balance(&state, &clock) - before - 25.0 / 24.0).abs() < 0.005);
+}
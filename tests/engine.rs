@@ -0,0 +1,564 @@
+//! The engine driven by a `FakeClock`, so every balance is known in advance.
+//! All queues start out with the default income of $1 per day.
+
+use chrono::prelude::*;
+use chrono::Duration;
+use fraction::ToPrimitive;
+
+use sq::clock::Clock;
+use sq::clock::FakeClock;
+use sq::engine;
+use sq::types::AllocationRule;
+use sq::types::Cap;
+use sq::types::Expiry;
+use sq::types::Income;
+use sq::types::Item;
+use sq::types::Overdraft;
+use sq::types::Policy;
+use sq::types::Share;
+use sq::types::SharedIncome;
+use sq::types::M;
+use sq::State;
+
+fn start() -> DateTime<Local> {
+    DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Local)
+}
+
+/// A fresh state as of `start`, with a second queue called `other`.
+fn setup() -> (State, FakeClock) {
+    let clock = FakeClock::new(start());
+    let mut state = State::new(clock.now());
+    engine::create_queue(&mut state, "other", &clock).unwrap();
+    (state, clock)
+}
+
+fn balance(state: &State, name: &str, clock: &FakeClock) -> f64 {
+    let (_, balance) = engine::calculate_current_amount(state.queue(name).unwrap(), clock).unwrap();
+    balance.to_f64().unwrap()
+}
+
+fn assert_balance(state: &State, name: &str, clock: &FakeClock, expected: f64) {
+    let actual = balance(state, name, clock);
+    assert!(
+        (actual - expected).abs() < 0.005,
+        "balance of {} is ${:.2}, expected ${:.2}",
+        name,
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn accrues_income_over_time() {
+    let (state, clock) = setup();
+    assert_balance(&state, "default", &clock, 0.0);
+
+    clock.advance(Duration::days(10));
+    assert_balance(&state, "default", &clock, 10.0);
+
+    clock.advance(Duration::hours(12));
+    assert_balance(&state, "default", &clock, 10.5);
+}
+
+#[test]
+fn settling_keeps_the_balance() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(3));
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    assert_balance(&state, "default", &clock, 3.0);
+
+    clock.advance(Duration::days(2));
+    assert_balance(&state, "default", &clock, 5.0);
+    assert_eq!(state.queue("default").unwrap().ledger.len(), 1);
+}
+
+#[test]
+fn clock_behind_last_calculation_accrues_nothing() {
+    let (mut state, clock) = setup();
+    clock.set(start() - Duration::days(5));
+    assert_balance(&state, "default", &clock, 0.0);
+
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    clock.set(start() + Duration::days(1));
+    assert_balance(&state, "default", &clock, 1.0);
+}
+
+#[test]
+fn pause_stops_accrual_until_unpaused() {
+    let (mut state, clock) = setup();
+    let queue = state.queue_mut("default").unwrap();
+    clock.advance(Duration::days(2));
+    engine::pause(queue, None, &clock).unwrap();
+    clock.advance(Duration::days(3));
+    assert_balance(&state, "default", &clock, 2.0);
+
+    engine::unpause(state.queue_mut("default").unwrap(), &clock).unwrap();
+    clock.advance(Duration::days(1));
+    assert_balance(&state, "default", &clock, 3.0);
+}
+
+#[test]
+fn pause_with_an_end_resumes_on_its_own() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(1));
+    let until = clock.now() + Duration::days(2);
+    engine::pause(state.queue_mut("default").unwrap(), Some(until), &clock).unwrap();
+
+    clock.advance(Duration::days(1));
+    assert_balance(&state, "default", &clock, 1.0);
+    clock.advance(Duration::days(3));
+    assert_balance(&state, "default", &clock, 3.0);
+}
+
+#[test]
+fn global_pause_stops_every_queue() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(1));
+    engine::pause_all(&mut state, &clock).unwrap();
+    clock.advance(Duration::days(3));
+    engine::unpause_all(&mut state, &clock).unwrap();
+    clock.advance(Duration::days(1));
+
+    assert_balance(&state, "default", &clock, 2.0);
+    assert_balance(&state, "other", &clock, 2.0);
+}
+
+#[test]
+fn own_pause_outlasts_global_pause() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(1));
+    engine::pause_all(&mut state, &clock).unwrap();
+    clock.advance(Duration::days(1));
+    engine::pause(state.queue_mut("other").unwrap(), None, &clock).unwrap();
+    clock.advance(Duration::days(1));
+    engine::unpause_all(&mut state, &clock).unwrap();
+    clock.advance(Duration::days(2));
+
+    assert_balance(&state, "default", &clock, 3.0);
+    assert_balance(&state, "other", &clock, 1.0);
+}
+
+#[test]
+fn long_pause_accrues_nothing_for_months() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(10));
+    let until = clock.now() + Duration::days(120);
+    engine::pause(state.queue_mut("default").unwrap(), Some(until), &clock).unwrap();
+
+    clock.advance(Duration::days(60));
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    assert_balance(&state, "default", &clock, 10.0);
+
+    clock.advance(Duration::days(90));
+    assert_balance(&state, "default", &clock, 40.0);
+}
+
+fn share(
+    state: &mut State,
+    rule: AllocationRule,
+    amount: f64,
+    values: [f64; 2],
+    clock: &FakeClock,
+) {
+    let shared = SharedIncome {
+        income: Income {
+            amount,
+            interval_in_days: 1,
+        },
+        rule,
+        shares: vec![
+            Share {
+                queue: "default".to_string(),
+                value: values[0],
+            },
+            Share {
+                queue: "other".to_string(),
+                value: values[1],
+            },
+        ],
+    };
+    engine::share_income(state, Some(shared), clock).unwrap();
+}
+
+#[test]
+fn shares_income_by_weight() {
+    let (mut state, clock) = setup();
+    share(&mut state, AllocationRule::Weights, 3.0, [2.0, 1.0], &clock);
+    clock.advance(Duration::days(2));
+
+    assert_balance(&state, "default", &clock, 4.0);
+    assert_balance(&state, "other", &clock, 2.0);
+}
+
+#[test]
+fn shares_fixed_amounts() {
+    let (mut state, clock) = setup();
+    share(&mut state, AllocationRule::Fixed, 3.0, [1.0, 0.5], &clock);
+    clock.advance(Duration::days(2));
+
+    assert_balance(&state, "default", &clock, 2.0);
+    assert_balance(&state, "other", &clock, 1.0);
+}
+
+#[test]
+fn waterfall_fills_queues_in_order() {
+    let (mut state, clock) = setup();
+    share(
+        &mut state,
+        AllocationRule::Waterfall,
+        1.0,
+        [2.0, 100.0],
+        &clock,
+    );
+    clock.advance(Duration::days(3));
+    engine::allocate(&mut state, &clock).unwrap();
+
+    assert_balance(&state, "default", &clock, 2.0);
+    assert_balance(&state, "other", &clock, 1.0);
+
+    clock.advance(Duration::days(1));
+    assert_balance(&state, "default", &clock, 2.0);
+    assert_balance(&state, "other", &clock, 2.0);
+}
+
+#[test]
+fn cap_stops_accrual() {
+    let (mut state, clock) = setup();
+    let policy = Policy {
+        cap: Some(Cap {
+            amount: 5.0,
+            spill_to: None,
+        }),
+        ..Policy::default()
+    };
+    engine::set_policy(&mut state, "default", policy, &clock).unwrap();
+    clock.advance(Duration::days(10));
+
+    assert_balance(&state, "default", &clock, 5.0);
+}
+
+#[test]
+fn cap_spills_over() {
+    let (mut state, clock) = setup();
+    let policy = Policy {
+        cap: Some(Cap {
+            amount: 5.0,
+            spill_to: Some("other".to_string()),
+        }),
+        ..Policy::default()
+    };
+    engine::set_policy(&mut state, "default", policy, &clock).unwrap();
+    clock.advance(Duration::days(8));
+    engine::enforce_policies(&mut state, &clock).unwrap();
+
+    assert_balance(&state, "default", &clock, 5.0);
+    assert_balance(&state, "other", &clock, 11.0);
+}
+
+#[test]
+fn simulation_keeps_to_the_cap() {
+    let (mut state, clock) = setup();
+    let policy = Policy {
+        cap: Some(Cap {
+            amount: 20.0,
+            spill_to: Some("other".to_string()),
+        }),
+        ..Policy::default()
+    };
+    engine::set_policy(&mut state, "default", policy, &clock).unwrap();
+    let queue = state.queue_mut("default").unwrap();
+    engine::add(queue, item("book", 15.0), false);
+    engine::add(queue, item("lamp", 30.0), false);
+
+    let until = clock.now() + Duration::days(90);
+    let simulation = engine::simulate(queue.clone(), until, &clock).unwrap();
+    let bought: Vec<_> = simulation.purchases.iter().map(|p| &p.item.name).collect();
+    assert_eq!(bought, vec!["book"]);
+    assert_eq!(simulation.balance, M::from(20.0));
+}
+
+#[test]
+fn simulation_lets_money_expire() {
+    let (mut state, clock) = setup();
+    set_expiry(&mut state, None, &clock);
+    let queue = state.queue("default").unwrap().clone();
+
+    let simulation = engine::simulate(queue, clock.now() + Duration::days(90), &clock).unwrap();
+    assert!((simulation.balance.to_f64().unwrap() - 30.0).abs() < 0.005);
+}
+
+fn set_expiry(state: &mut State, sweep_to: Option<&str>, clock: &FakeClock) {
+    let policy = Policy {
+        expiry: Some(Expiry {
+            after_days: 30,
+            sweep_to: sweep_to.map(str::to_string),
+        }),
+        ..Policy::default()
+    };
+    engine::set_policy(state, "default", policy, clock).unwrap();
+}
+
+fn expiring(state: &State, clock: &FakeClock) -> f64 {
+    let queue = state.queue("default").unwrap();
+    engine::expiring(queue, clock.now(), clock)
+        .unwrap()
+        .to_f64()
+        .unwrap()
+}
+
+#[test]
+fn expiry_catches_up_on_an_idle_queue() {
+    let (mut state, clock) = setup();
+    set_expiry(&mut state, None, &clock);
+    clock.advance(Duration::days(92));
+    assert!((expiring(&state, &clock) - 62.0).abs() < 0.005);
+
+    engine::enforce_policies(&mut state, &clock).unwrap();
+    assert_balance(&state, "default", &clock, 30.0);
+
+    clock.advance(Duration::days(1));
+    engine::enforce_policies(&mut state, &clock).unwrap();
+    assert_balance(&state, "default", &clock, 30.0);
+}
+
+#[test]
+fn expiry_splits_settled_accrual_at_the_cutoff() {
+    let (mut state, clock) = setup();
+    set_expiry(&mut state, None, &clock);
+    clock.advance(Duration::days(20));
+    engine::update_accumulation(state.queue_mut("default").unwrap(), &clock).unwrap();
+    clock.advance(Duration::days(20));
+
+    assert!((expiring(&state, &clock) - 10.0).abs() < 0.005);
+}
+
+#[test]
+fn expiry_sweeps_into_another_queue() {
+    let (mut state, clock) = setup();
+    set_expiry(&mut state, Some("other"), &clock);
+    clock.advance(Duration::days(40));
+    engine::enforce_policies(&mut state, &clock).unwrap();
+
+    assert_balance(&state, "default", &clock, 30.0);
+    assert_balance(&state, "other", &clock, 50.0);
+}
+
+#[test]
+fn rejects_policies_that_arent_numbers() {
+    let (mut state, clock) = setup();
+    let cap = Policy {
+        cap: Some(Cap {
+            amount: f64::NAN,
+            spill_to: None,
+        }),
+        ..Policy::default()
+    };
+    let overdraft = Policy {
+        overdraft: Overdraft::UpTo { limit: f64::NAN },
+        ..Policy::default()
+    };
+    for policy in [cap, overdraft] {
+        assert!(engine::set_policy(&mut state, "default", policy, &clock).is_err());
+    }
+}
+
+fn set_overdraft(state: &mut State, overdraft: Overdraft, clock: &FakeClock) {
+    let policy = Policy {
+        overdraft,
+        ..Policy::default()
+    };
+    engine::set_policy(state, "default", policy, clock).unwrap();
+}
+
+fn withdraw(state: &mut State, amount: f64, force: bool, clock: &FakeClock) -> sq::Result<()> {
+    let queue = state.queue_mut("default").unwrap();
+    engine::withdraw(queue, M::from(amount), None, force, clock).map(|_| ())
+}
+
+#[test]
+fn overdraft_needs_force() {
+    let (mut state, clock) = setup();
+    clock.advance(Duration::days(2));
+
+    assert!(withdraw(&mut state, 3.0, false, &clock).is_err());
+    withdraw(&mut state, 3.0, true, &clock).unwrap();
+    assert_balance(&state, "default", &clock, -1.0);
+}
+
+#[test]
+fn overdraft_forbidden() {
+    let (mut state, clock) = setup();
+    set_overdraft(&mut state, Overdraft::Forbid, &clock);
+    clock.advance(Duration::days(2));
+
+    assert!(withdraw(&mut state, 3.0, true, &clock).is_err());
+    withdraw(&mut state, 2.0, true, &clock).unwrap();
+    assert_balance(&state, "default", &clock, 0.0);
+}
+
+#[test]
+fn overdraft_up_to_limit() {
+    let (mut state, clock) = setup();
+    set_overdraft(&mut state, Overdraft::UpTo { limit: 5.0 }, &clock);
+    clock.advance(Duration::days(2));
+
+    withdraw(&mut state, 6.0, true, &clock).unwrap();
+    assert!(withdraw(&mut state, 2.0, true, &clock).is_err());
+    withdraw(&mut state, 1.0, true, &clock).unwrap();
+    assert_balance(&state, "default", &clock, -5.0);
+}
+
+#[test]
+fn debt_is_paid_off_by_accrual() {
+    let (mut state, clock) = setup();
+    withdraw(&mut state, 4.0, true, &clock).unwrap();
+    let debt = engine::debt(state.queue("default").unwrap(), &clock)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(debt.amount, M::from(4.0));
+    assert_eq!(debt.repaid_at, Some(start() + Duration::days(4)));
+}
+
+fn item(name: &str, amount: f64) -> Item {
+    Item {
+        id: String::new(),
+        name: name.to_string(),
+        amount: M::from(amount),
+        purchase_link: None,
+        time_purchased: None,
+        notes: None,
+        tags: Vec::new(),
+        price_history: Vec::new(),
+    }
+}
+
+/// A queue holding items `a`, `b`, `c` and `d`, in that order.
+fn lettered() -> State {
+    let (mut state, _) = setup();
+    let queue = state.queue_mut("default").unwrap();
+    for name in ["a", "b", "c", "d"] {
+        engine::add(queue, item(name, 1.0), false);
+    }
+    state
+}
+
+fn names(state: &State) -> Vec<&str> {
+    let queue = state.queue("default").unwrap();
+    queue
+        .future_purchases
+        .iter()
+        .map(|i| i.name.as_str())
+        .collect()
+}
+
+#[test]
+fn finds_items_by_position_or_id() {
+    let state = lettered();
+    let queue = state.queue("default").unwrap();
+    let id = queue.future_purchases[2].id.to_uppercase();
+
+    assert_eq!(engine::find_item(queue, "2").unwrap(), 1);
+    assert_eq!(engine::find_item(queue, &id).unwrap(), 2);
+    assert!(engine::find_item(queue, "0").is_err());
+    assert!(engine::find_item(queue, "5").is_err());
+    assert!(engine::find_item(queue, "nope").is_err());
+}
+
+#[test]
+fn bumps_the_targeted_item() {
+    let mut state = lettered();
+    let queue = state.queue_mut("default").unwrap();
+    // Never to the very end, so the third item only has one place to go.
+    let event = engine::bump(queue, Some("2"), &mut rand::thread_rng()).unwrap();
+
+    assert!(matches!(
+        event,
+        engine::Event::Bumped {
+            from: 2,
+            position: 3,
+            ..
+        }
+    ));
+    assert_eq!(names(&state), vec!["a", "c", "b", "d"]);
+
+    let queue = state.queue_mut("default").unwrap();
+    assert!(engine::bump(queue, Some("4"), &mut rand::thread_rng()).is_err());
+}
+
+#[test]
+fn moves_items_either_way() {
+    let mut state = lettered();
+    let queue = state.queue_mut("default").unwrap();
+    engine::move_item(queue, "1", 3).unwrap();
+    assert_eq!(names(&state), vec!["b", "c", "a", "d"]);
+
+    let queue = state.queue_mut("default").unwrap();
+    engine::move_item(queue, "4", 1).unwrap();
+    assert_eq!(names(&state), vec!["d", "b", "c", "a"]);
+
+    let queue = state.queue_mut("default").unwrap();
+    assert!(engine::move_item(queue, "1", 0).is_err());
+    assert!(engine::move_item(queue, "1", 5).is_err());
+}
+
+#[test]
+fn forecast_too_far_out_is_never() {
+    let (mut state, clock) = setup();
+    let queue = state.queue_mut("default").unwrap();
+    engine::add(queue, item("close", 2.0), false);
+    engine::add(queue, item("far", 1e9), false);
+    engine::add(queue, item("farther", 1e12), false);
+    let forecasts = engine::forecast(queue, &clock).unwrap();
+
+    let at: Vec<_> = forecasts.iter().map(|f| f.affordable_at).collect();
+    assert_eq!(at, vec![Some(start() + Duration::days(2)), None, None]);
+}
+
+#[test]
+fn debt_too_large_is_never_paid_off() {
+    let (mut state, clock) = setup();
+    withdraw(&mut state, 1e12, true, &clock).unwrap();
+    let debt = engine::debt(state.queue("default").unwrap(), &clock)
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(debt.repaid_at, None);
+}
+
+#[test]
+fn rejects_incomes_that_cant_accrue() {
+    let (mut state, clock) = setup();
+    let queue = state.queue_mut("default").unwrap();
+    for (amount, interval_in_days) in [(1.0, 0), (f64::NAN, 1), (f64::INFINITY, 1), (-1.0, 1)] {
+        let income = Income {
+            amount,
+            interval_in_days,
+        };
+        assert!(engine::set_income(queue, income, &clock).is_err());
+    }
+    clock.advance(Duration::days(1));
+    assert_balance(&state, "default", &clock, 1.0);
+}
+
+#[test]
+fn rejects_shares_that_cant_accrue() {
+    let (mut state, clock) = setup();
+    for (interval_in_days, value) in [(0, 1.0), (1, f64::NAN), (1, -1.0)] {
+        let shared = SharedIncome {
+            income: Income {
+                amount: 1.0,
+                interval_in_days,
+            },
+            rule: AllocationRule::Weights,
+            shares: vec![Share {
+                queue: "other".to_string(),
+                value,
+            }],
+        };
+        assert!(engine::share_income(&mut state, Some(shared), &clock).is_err());
+    }
+    assert!(state.shared_income.is_none());
+}
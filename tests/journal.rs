@@ -0,0 +1,156 @@
+//! Undoing and redoing changes made through a session.
+
+mod common;
+
+use std::fs;
+
+use chrono::prelude::*;
+use serde_json::Value;
+
+use common::Scratch;
+use sq::clock::FakeClock;
+use sq::engine;
+use sq::journal::Journal;
+use sq::journal::Snapshot;
+use sq::notice;
+use sq::notice::Notify;
+use sq::storage::Storage;
+use sq::types::Item;
+use sq::types::M;
+use sq::Result;
+use sq::Session;
+use sq::State;
+
+type Step = fn(&Journal, &dyn Storage, &State, Notify) -> Result<Option<Snapshot>>;
+
+fn session(scratch: &Scratch) -> Session {
+    let now = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Local);
+    Session::open(Some(&scratch.path("state.json")))
+        .unwrap()
+        .with_clock(FakeClock::new(now))
+}
+
+fn add(session: &Session, name: &str) {
+    let item = Item {
+        id: String::new(),
+        name: name.to_string(),
+        amount: M::from(1.0),
+        purchase_link: None,
+        time_purchased: None,
+        notes: None,
+        tags: Vec::new(),
+        price_history: Vec::new(),
+    };
+    session
+        .update_selected_queue("add", |queue| Ok(engine::add(queue, item, false)))
+        .unwrap();
+}
+
+/// Take one step through the journal, the way `sq undo` and `sq redo` do.
+/// The state it stepped to, if there was anywhere to go.
+fn step(session: &Session, step: Step) -> Option<Value> {
+    let (store, _lock, current) = session.begin_change().unwrap();
+    let snapshot = step(&Journal::open(&*store), &*store, &current, &notice::ignore).unwrap();
+    snapshot.map(|snapshot| json(&snapshot.state))
+}
+
+fn json(state: &State) -> Value {
+    serde_json::to_value(state).unwrap()
+}
+
+fn loaded(session: &Session) -> Value {
+    json(&session.load().unwrap())
+}
+
+fn queue_names(session: &Session) -> Vec<String> {
+    let state = session.load().unwrap();
+    state
+        .queues
+        .iter()
+        .map(|queue| queue.name.clone())
+        .collect()
+}
+
+#[test]
+fn undoes_and_redoes_an_item_change() {
+    let scratch = Scratch::new("journal-item");
+    let session = session(&scratch);
+    add(&session, "a");
+    let before = loaded(&session);
+    add(&session, "b");
+    let after = loaded(&session);
+
+    assert_eq!(step(&session, Journal::undo), Some(before.clone()));
+    assert_eq!(loaded(&session), before);
+    assert_eq!(step(&session, Journal::redo), Some(after.clone()));
+    assert_eq!(loaded(&session), after);
+    assert_eq!(step(&session, Journal::redo), None);
+}
+
+#[test]
+fn undoes_creating_and_deleting_queues_in_order() {
+    let scratch = Scratch::new("journal-queues");
+    let session = session(&scratch);
+    for name in ["other", "third"] {
+        session
+            .update_state("queue new", |state| {
+                engine::create_queue(state, name, session.clock())
+            })
+            .unwrap();
+    }
+    session
+        .update_state("queue delete", |state| {
+            engine::delete_queue(state, "other", None, session.clock())
+        })
+        .unwrap();
+    assert_eq!(queue_names(&session), vec!["default", "third"]);
+
+    step(&session, Journal::undo).unwrap();
+    assert_eq!(queue_names(&session), vec!["default", "other", "third"]);
+    step(&session, Journal::undo).unwrap();
+    assert_eq!(queue_names(&session), vec!["default", "other"]);
+    step(&session, Journal::redo).unwrap();
+    step(&session, Journal::redo).unwrap();
+    assert_eq!(queue_names(&session), vec!["default", "third"]);
+}
+
+#[test]
+fn keeps_only_the_latest_changes() {
+    let scratch = Scratch::new("journal-length");
+    let session = session(&scratch);
+    for i in 0..25 {
+        add(&session, &i.to_string());
+    }
+    let entries = fs::read_dir(scratch.path("state.json.journal")).unwrap();
+    assert_eq!(entries.count(), 20);
+
+    for _ in 0..20 {
+        assert!(step(&session, Journal::undo).is_some());
+    }
+    assert_eq!(step(&session, Journal::undo), None);
+    let state = session.load().unwrap();
+    assert_eq!(state.queue("default").unwrap().future_purchases.len(), 5);
+}
+
+#[test]
+fn a_new_change_clears_what_could_be_redone() {
+    let scratch = Scratch::new("journal-redo");
+    let session = session(&scratch);
+    add(&session, "a");
+    add(&session, "b");
+    step(&session, Journal::undo).unwrap();
+    add(&session, "c");
+
+    assert_eq!(step(&session, Journal::redo), None);
+    let state = session.load().unwrap();
+    let names: Vec<_> = state
+        .queue("default")
+        .unwrap()
+        .future_purchases
+        .iter()
+        .map(|i| &i.name)
+        .collect();
+    assert_eq!(names, vec!["a", "c"]);
+}
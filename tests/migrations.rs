@@ -0,0 +1,160 @@
+//! Older state files, brought forward to the current schema on load.
+
+mod common;
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+
+use serde_json::json;
+use serde_json::Value;
+
+use common::Scratch;
+use sq::config::Config;
+use sq::config::Location;
+use sq::error::SqError;
+use sq::notice::Notice;
+use sq::storage;
+use sq::types::M;
+use sq::Result;
+use sq::State;
+
+fn money(amount: f64) -> Value {
+    serde_json::to_value(M::from(amount)).unwrap()
+}
+
+/// A state file as written before sq knew about more than one queue.
+fn v0() -> Value {
+    json!({
+        "income": { "amount": 2.0, "interval_in_days": 1 },
+        "last_calculation": "Thu, 01 Jan 2026 00:00:00 +0000",
+        "current_amount": money(12.5),
+        "future_purchases": [{
+            "name": "lamp",
+            "amount": money(30.0),
+            "purchase_link": null,
+            "time_purchased": null,
+        }],
+        "past_purchases": [],
+        "paused": true,
+    })
+}
+
+/// A state file with queues, from before it carried its schema version.
+fn v1() -> Value {
+    json!({
+        "queues": [{
+            "income": { "amount": 2.0, "interval_in_days": 1 },
+            "name": "default",
+            "last_calculation": "Thu, 01 Jan 2026 00:00:00 +0000",
+            "current_balance": money(12.5),
+            "future_purchases": [],
+            "past_purchases": [],
+            "paused": false,
+        }],
+        "currently_selected": "default",
+        "globally_paused": false,
+    })
+}
+
+/// Write `content` as the state file and load it, along with what the load
+/// had to say.
+fn load(path: &Path, content: &Value) -> (Result<Option<State>>, Vec<Notice>) {
+    fs::write(path, serde_json::to_string_pretty(content).unwrap()).unwrap();
+    let notices = RefCell::new(Vec::new());
+    let notify = |notice: &Notice| notices.borrow_mut().push(notice.clone());
+    let loaded = storage::open(
+        &Location::File(path.to_path_buf()),
+        &Config::default(),
+        &notify,
+    )
+    .and_then(|store| store.load(&notify));
+    (loaded, notices.into_inner())
+}
+
+fn read(path: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn copies(scratch: &Scratch) -> Vec<u32> {
+    (0..10)
+        .filter(|version| scratch.path(&format!("state.json.v{version}.bak")).exists())
+        .collect()
+}
+
+#[test]
+fn migrates_v0_to_the_current_schema() {
+    let scratch = Scratch::new("migrate-v0");
+    let path = scratch.path("state.json");
+    let (loaded, notices) = load(&path, &v0());
+    let state = loaded.unwrap().unwrap();
+
+    assert_eq!(state.schema_version, 5);
+    assert_eq!(state.currently_selected, "default");
+    let queue = state.queue("default").unwrap();
+    assert_eq!(queue.current_balance, M::from(12.5));
+    assert_eq!(queue.ledger.len(), 1);
+    assert_eq!(queue.ledger[0].amount, M::from(12.5));
+    assert_eq!(queue.pauses.len(), 1);
+    assert_eq!(queue.pauses[0].until, None);
+    assert_eq!(queue.future_purchases[0].id.len(), 4);
+
+    assert_eq!(
+        notices,
+        vec![Notice::Migrated {
+            path: path.clone(),
+            from: 0,
+            to: 5,
+        }]
+    );
+    assert_eq!(read(&path)["schema_version"], json!(5));
+}
+
+#[test]
+fn keeps_a_copy_from_before_each_step() {
+    let scratch = Scratch::new("migrate-copies");
+    load(&scratch.path("state.json"), &v0()).0.unwrap();
+
+    assert_eq!(copies(&scratch), vec![0, 1, 2, 3, 4]);
+    assert_eq!(read(&scratch.path("state.json.v0.bak")), v0());
+    let v1 = read(&scratch.path("state.json.v1.bak"));
+    assert_eq!(v1["queues"][0]["current_balance"], money(12.5));
+    assert_eq!(v1["schema_version"], json!(1));
+}
+
+#[test]
+fn recognises_v1_without_a_schema_version() {
+    let scratch = Scratch::new("migrate-v1");
+    let path = scratch.path("state.json");
+    let (loaded, notices) = load(&path, &v1());
+
+    assert_eq!(loaded.unwrap().unwrap().schema_version, 5);
+    assert!(matches!(notices[..], [Notice::Migrated { from: 1, .. }]));
+    assert_eq!(copies(&scratch), vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn refuses_a_newer_schema() {
+    let scratch = Scratch::new("migrate-newer");
+    let path = scratch.path("state.json");
+    let mut newer = v1();
+    newer["schema_version"] = json!(6);
+    let (loaded, notices) = load(&path, &newer);
+
+    assert!(matches!(loaded, Err(SqError::Storage(_))));
+    assert!(notices.is_empty());
+    assert_eq!(read(&path), newer);
+    assert!(copies(&scratch).is_empty());
+}
+
+#[test]
+fn failed_migration_leaves_no_copies() {
+    let scratch = Scratch::new("migrate-failed");
+    let mut broken = v0();
+    // Carried over as is, so only parsing the final state trips over it.
+    broken["income"] = json!("lots");
+    let (loaded, _) = load(&scratch.path("state.json"), &broken);
+
+    assert!(matches!(loaded, Err(SqError::Corrupt { backup: None, .. })));
+    assert!(copies(&scratch).is_empty());
+}
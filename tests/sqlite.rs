@@ -0,0 +1,123 @@
+//! The SQLite backend, which writes queues and their items row by row.
+
+mod common;
+
+use std::cell::RefCell;
+use std::fs;
+
+use chrono::prelude::*;
+use serde_json::Value;
+
+use common::Scratch;
+use sq::clock::Clock;
+use sq::clock::FakeClock;
+use sq::config::Backend;
+use sq::config::Config;
+use sq::config::Location;
+use sq::engine;
+use sq::notice;
+use sq::notice::Notice;
+use sq::storage;
+use sq::storage::JsonStorage;
+use sq::storage::SqliteStorage;
+use sq::storage::Storage;
+use sq::types::Item;
+use sq::types::M;
+use sq::State;
+
+/// A state with two queues holding a few items, and some history for the
+/// first one.
+fn state() -> State {
+    let now = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Local);
+    let clock = FakeClock::new(now);
+    let mut state = State::new(clock.now());
+    engine::create_queue(&mut state, "other", &clock).unwrap();
+    for (queue, name) in [
+        ("default", "a"),
+        ("default", "b"),
+        ("default", "c"),
+        ("other", "d"),
+    ] {
+        let item = Item {
+            id: String::new(),
+            name: name.to_string(),
+            amount: M::from(1.0),
+            purchase_link: None,
+            time_purchased: None,
+            notes: None,
+            tags: Vec::new(),
+            price_history: Vec::new(),
+        };
+        engine::add(state.queue_mut(queue).unwrap(), item, false);
+    }
+    clock.advance(chrono::Duration::days(3));
+    let queue = state.queue_mut("default").unwrap();
+    let id = queue.future_purchases[0].id.clone();
+    engine::buy(queue, &id, M::from(1.0), false, &clock).unwrap();
+    state
+}
+
+fn json(state: &State) -> Value {
+    serde_json::to_value(state).unwrap()
+}
+
+fn reloaded(store: &dyn Storage) -> Value {
+    json(&store.load(&notice::ignore).unwrap().unwrap())
+}
+
+#[test]
+fn saves_single_queues() {
+    let scratch = Scratch::new("sqlite-queues");
+    let store = SqliteStorage::new(scratch.path("state.db"));
+    assert!(store.load(&notice::ignore).unwrap().is_none());
+
+    let mut state = state();
+    store.save(&state).unwrap();
+    assert_eq!(reloaded(&store), json(&state));
+
+    let queue = state.queue_mut("default").unwrap();
+    queue.future_purchases.pop_back();
+    queue.ledger.clear();
+    store.save_queue(&state, "default").unwrap();
+    assert_eq!(reloaded(&store), json(&state));
+
+    engine::rename_queue(&mut state, "other", "books").unwrap();
+    engine::rename_queue(&mut state, "default", "main").unwrap();
+    store.save_queue(&state, "books").unwrap();
+    assert_eq!(reloaded(&store), json(&state));
+}
+
+#[test]
+fn imports_the_json_state_once() {
+    let scratch = Scratch::new("sqlite-import");
+    let state = state();
+    JsonStorage::new(scratch.path("state.json"))
+        .save(&state)
+        .unwrap();
+    let config = Config {
+        storage: Backend::Sqlite,
+        ..Config::default()
+    };
+
+    let notices = RefCell::new(Vec::new());
+    let notify = |notice: &Notice| notices.borrow_mut().push(notice.clone());
+    let location = Location::Dir(scratch.dir().to_path_buf());
+    let store = storage::open(&location, &config, &notify).unwrap();
+    assert_eq!(store.path(), scratch.path("state.db"));
+    assert_eq!(reloaded(&*store), json(&state));
+    assert_eq!(
+        notices.take(),
+        vec![Notice::Imported {
+            from: scratch.path("state.json"),
+            into: scratch.path("state.db"),
+        }]
+    );
+
+    // Once there's a database, the JSON file isn't looked at again.
+    fs::write(scratch.path("state.json"), "not even JSON").unwrap();
+    let store = storage::open(&location, &config, &notify).unwrap();
+    assert_eq!(reloaded(&*store), json(&state));
+    assert!(notices.take().is_empty());
+}